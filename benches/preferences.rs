@@ -3,6 +3,7 @@ use criterion::criterion_main;
 use criterion::Criterion;
 
 use fluent_testing::get_scenarios;
+use l10nregistry::registry::ResourceId;
 use l10nregistry::testing::TestFileFetcher;
 
 use unic_langid::LanguageIdentifier;
@@ -13,7 +14,11 @@ fn preferences_bench(c: &mut Criterion) {
     let mut group = c.benchmark_group("registry/scenarios");
 
     for scenario in get_scenarios() {
-        let res_ids = scenario.res_ids.clone();
+        let res_ids: Vec<ResourceId> = scenario
+            .res_ids
+            .iter()
+            .map(|res_id| res_id.as_str().into())
+            .collect();
 
         let locales: Vec<LanguageIdentifier> = scenario
             .locales
@@ -36,7 +41,7 @@ fn preferences_bench(c: &mut Criterion) {
 
             let rt = tokio::runtime::Runtime::new().unwrap();
 
-            group.bench_function(&format!("{}/async/first_bundle", scenario.name), |b| {
+            group.bench_function(format!("{}/async/first_bundle", scenario.name), |b| {
                 b.iter(|| {
                     rt.block_on(async {
                         let reg = fetcher.get_registry(&scenario);
@@ -6,7 +6,9 @@ use futures::stream::Collect;
 use futures::stream::FuturesOrdered;
 use futures::StreamExt;
 use l10nregistry::solver::testing::get_scenarios;
-use l10nregistry::solver::{AsyncTester, ParallelProblemSolver, SerialProblemSolver, SyncTester};
+use l10nregistry::solver::{
+    AsyncTester, Lookahead, ParallelProblemSolver, SerialProblemSolver, SolverOptions, SyncTester,
+};
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -94,7 +96,7 @@ fn solver_bench(c: &mut Criterion) {
             values: scenario.values.clone(),
         };
 
-        group.bench_function(&format!("serial/{}", &scenario.name), |b| {
+        group.bench_function(format!("serial/{}", &scenario.name), |b| {
             b.iter(|| {
                 let mut gen = SerialProblemSolver::new(scenario.width, scenario.depth);
                 while let Ok(Some(_)) = gen.try_next(&tester, false) {}
@@ -104,13 +106,23 @@ fn solver_bench(c: &mut Criterion) {
         {
             let rt = tokio::runtime::Runtime::new().unwrap();
 
-            group.bench_function(&format!("parallel/{}", &scenario.name), |b| {
-                b.iter(|| {
-                    let gen = ParallelProblemSolver::new(scenario.width, scenario.depth);
-                    let mut t = TestStream::new(gen, &tester);
-                    rt.block_on(async { while let Some(_) = t.next().await {} });
-                })
-            });
+            for (name, lookahead) in &[
+                ("parallel", Lookahead::None),
+                ("parallel-lookahead-4", Lookahead::Candidates(4)),
+                ("parallel-eager", Lookahead::Eager),
+            ] {
+                group.bench_function(format!("{}/{}", name, &scenario.name), |b| {
+                    b.iter(|| {
+                        let mut gen = ParallelProblemSolver::new(scenario.width, scenario.depth);
+                        gen.set_options(SolverOptions {
+                            lookahead: *lookahead,
+                            ..Default::default()
+                        });
+                        let mut t = TestStream::new(gen, &tester);
+                        rt.block_on(async { while t.next().await.is_some() {} });
+                    })
+                });
+            }
         }
     }
     group.finish();
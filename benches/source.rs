@@ -34,7 +34,7 @@ fn source_bench(c: &mut Criterion) {
             b.iter(|| {
                 for source in &sources {
                     for res_id in &res_ids {
-                        source.has_file(&locales[0], &res_id);
+                        source.has_file(&locales[0], res_id);
                     }
                 }
             })
@@ -44,7 +44,7 @@ fn source_bench(c: &mut Criterion) {
             b.iter(|| {
                 for source in &sources {
                     for res_id in &res_ids {
-                        source.fetch_file_sync(&locales[0], &res_id, false);
+                        source.fetch_file_sync(&locales[0], res_id, false);
                     }
                 }
             })
@@ -1,25 +1,167 @@
 use fluent_bundle::FluentError;
 use std::error::Error;
+use std::io;
 use unic_langid::LanguageIdentifier;
 
+#[cfg(feature = "serde")]
+mod serialization;
+
+/// With the `serde` feature, the errors are serialized with a `"type"` field
+/// holding the name of the variant. The `kind` of an `IoError` is serialized
+/// with its name, and the kinds this crate doesn't know of are deserialized
+/// as `Other`, so their round trip is lossy.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum L10nRegistryError {
     FluentError {
         path: String,
         loc: Option<(usize, usize)>,
+        #[cfg_attr(feature = "serde", serde(with = "serialization::fluent_error"))]
         error: FluentError,
     },
+    /// An entry of a resource has the same id as an entry already added to
+    /// the bundle, and has been skipped.
+    OverridingEntry {
+        source: String,
+        /// The full path of the resource.
+        path: String,
+        loc: Option<(usize, usize)>,
+        /// The full path of the resource which has added the entry to the
+        /// bundle first, if it's known.
+        original_path: Option<String>,
+        kind: EntryKind,
+        id: String,
+    },
+    /// A required resource is missing in all sources. The locale is
+    /// skipped.
     MissingResource {
         locale: LanguageIdentifier,
         res_id: String,
+        /// Why each of the sources of the registry doesn't provide the
+        /// resource, in the order they are tried.
+        sources: Vec<SourceLookup>,
+    },
+    /// An optional resource is missing in all sources. Bundles for the
+    /// locale are generated without it.
+    MissingOptionalResource {
+        locale: LanguageIdentifier,
+        res_id: String,
+    },
+    IoError {
+        path: String,
+        #[cfg_attr(feature = "serde", serde(with = "serialization::io_error_kind"))]
+        kind: io::ErrorKind,
+        message: String,
+    },
+    ManifestError {
+        path: String,
+        line: usize,
+        message: String,
+    },
+}
+
+/// The kind of an entry of a bundle.
+///
+/// It mirrors the kind of a `FluentError::Overriding`, which `fluent_bundle`
+/// doesn't export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum EntryKind {
+    Message,
+    Term,
+    Function,
+}
+
+impl EntryKind {
+    /// Convert the kind of a `FluentError::Overriding`, which can only be
+    /// told apart by its name.
+    pub(crate) fn from_fluent(kind: &impl std::fmt::Display) -> Self {
+        match kind.to_string().as_str() {
+            "term" => Self::Term,
+            "function" => Self::Function,
+            _ => Self::Message,
+        }
+    }
+}
+
+impl std::fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Message => f.write_str("message"),
+            Self::Term => f.write_str("term"),
+            Self::Function => f.write_str("function"),
+        }
+    }
+}
+
+/// The result of looking up a resource in a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LookupStatus {
+    Available,
+    /// The source doesn't declare the locale.
+    LocaleNotDeclared,
+    /// The index of the source doesn't list the resource.
+    NotInIndex,
+    /// The resource has been fetched and wasn't found.
+    NotFound,
+    /// The resource couldn't be fetched because of an I/O error.
+    IoError {
+        #[cfg_attr(feature = "serde", serde(with = "serialization::io_error_kind"))]
+        kind: io::ErrorKind,
     },
+    /// The resource hasn't been fetched yet, or is being fetched.
+    Unknown,
+}
+
+/// Describes how a resource has been looked up in a source.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceLookup {
+    pub source: String,
+    /// The path template of the source, such as `"browser/{locale}/"`.
+    pub template: String,
+    /// The full path of the resource in the source.
+    pub path: String,
+    pub status: LookupStatus,
+}
+
+impl std::fmt::Display for SourceLookup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({} as {}): ", self.source, self.template, self.path)?;
+        match self.status {
+            LookupStatus::Available => write!(f, "available"),
+            LookupStatus::LocaleNotDeclared => write!(f, "locale not declared"),
+            LookupStatus::NotInIndex => write!(f, "not in the index"),
+            LookupStatus::NotFound => write!(f, "not found"),
+            LookupStatus::IoError { kind } => write!(f, "I/O error ({:?})", kind),
+            LookupStatus::Unknown => write!(f, "not fetched"),
+        }
+    }
 }
 
 impl std::fmt::Display for L10nRegistryError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::MissingResource { locale, res_id } => {
-                write!(f, "Missing resource in locale {}: {}", locale, res_id)
+            Self::MissingResource {
+                locale,
+                res_id,
+                sources,
+            } => {
+                write!(f, "Missing resource in locale {}: {}", locale, res_id)?;
+                for lookup in sources {
+                    write!(f, "\n  {}", lookup)?;
+                }
+                Ok(())
+            }
+            Self::MissingOptionalResource { locale, res_id } => {
+                write!(
+                    f,
+                    "Missing optional resource in locale {}: {}",
+                    locale, res_id
+                )
             }
             Self::FluentError { path, loc, error } => {
                 if let Some(loc) = loc {
@@ -32,6 +174,38 @@ impl std::fmt::Display for L10nRegistryError {
                     write!(f, "Fluent Error in {}: {}", path, error)
                 }
             }
+            Self::OverridingEntry {
+                source,
+                path,
+                loc,
+                original_path,
+                kind,
+                id,
+            } => {
+                write!(f, "Fluent Error in {} from {}", path, source)?;
+                if let Some(loc) = loc {
+                    write!(f, "[line: {}, col: {}]", loc.0, loc.1)?;
+                }
+                write!(f, ": Attempt to override an existing {}: \"{}\".", kind, id)?;
+                if let Some(original_path) = original_path {
+                    write!(f, " (first defined in {})", original_path)?;
+                }
+                Ok(())
+            }
+            Self::IoError {
+                path,
+                kind,
+                message,
+            } => {
+                write!(f, "I/O Error in {} ({:?}): {}", path, kind, message)
+            }
+            Self::ManifestError {
+                path,
+                line,
+                message,
+            } => {
+                write!(f, "Manifest Error in {}[line: {}]: {}", path, line, message)
+            }
         }
     }
 }
@@ -39,10 +213,29 @@ impl std::fmt::Display for L10nRegistryError {
 impl Error for L10nRegistryError {}
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum L10nRegistrySetupError {
     RegistryLocked,
-    DuplicatedSource { name: String },
-    MissingSource { name: String },
+    DuplicatedSource {
+        name: String,
+    },
+    MissingSource {
+        name: String,
+    },
+    /// A registry configuration can't be parsed or serialized.
+    InvalidConfig {
+        message: String,
+    },
+    /// The path template of a source can't be parsed.
+    InvalidPathTemplate {
+        template: String,
+        message: String,
+    },
+    /// A source can't be modified after it has been cloned.
+    SourceShared {
+        name: String,
+    },
 }
 
 impl std::fmt::Display for L10nRegistrySetupError {
@@ -55,6 +248,15 @@ impl std::fmt::Display for L10nRegistrySetupError {
             Self::MissingSource { name } => {
                 write!(f, "Cannot find a source with a name {}.", &name)
             }
+            Self::InvalidConfig { message } => {
+                write!(f, "Invalid registry configuration: {}", &message)
+            }
+            Self::InvalidPathTemplate { template, message } => {
+                write!(f, "Invalid path template {}: {}", &template, &message)
+            }
+            Self::SourceShared { name } => {
+                write!(f, "Can't modify the source {} when it's shared.", &name)
+            }
         }
     }
 }
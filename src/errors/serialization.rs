@@ -0,0 +1,303 @@
+//! Stable serde representations of the foreign types wrapped by the errors
+//! of this crate, used with `#[serde(with = "...")]`.
+
+use super::EntryKind;
+use crate::fluent::{FluentBundle, FluentError, FluentResource};
+use fluent_bundle::resolver::{errors::ReferenceKind, ResolverError};
+use fluent_bundle::{FluentArgs, FluentValue};
+use fluent_syntax::parser::{ErrorKind, ParserError};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{ops::Range, rc::Rc};
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ParserError")]
+struct ParserErrorDef {
+    pos: Range<usize>,
+    slice: Option<Range<usize>>,
+    #[serde(with = "ErrorKindDef")]
+    kind: ErrorKind,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ErrorKind")]
+enum ErrorKindDef {
+    ExpectedToken(char),
+    ExpectedCharRange { range: String },
+    ExpectedMessageField { entry_id: String },
+    ExpectedTermField { entry_id: String },
+    ForbiddenCallee,
+    MissingDefaultVariant,
+    MissingValue,
+    MultipleDefaultVariants,
+    MessageReferenceAsSelector,
+    TermReferenceAsSelector,
+    MessageAttributeAsSelector,
+    TermAttributeAsPlaceable,
+    UnterminatedStringLiteral,
+    PositionalArgumentFollowsNamed,
+    DuplicatedNamedArgument(String),
+    UnknownEscapeSequence(String),
+    InvalidUnicodeEscapeSequence(String),
+    UnbalancedClosingBrace,
+    ExpectedInlineExpression,
+    ExpectedSimpleExpressionAsSelector,
+    ExpectedLiteral,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ResolverError")]
+enum ResolverErrorDef {
+    Reference(#[serde(with = "ReferenceKindDef")] ReferenceKind),
+    NoValue(String),
+    MissingDefault,
+    Cyclic,
+    TooManyPlaceables,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ReferenceKind")]
+enum ReferenceKindDef {
+    Function {
+        id: String,
+    },
+    Message {
+        id: String,
+        attribute: Option<String>,
+    },
+    Term {
+        id: String,
+        attribute: Option<String>,
+    },
+    Variable {
+        id: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum FluentErrorDef {
+    Overriding { kind: EntryKind, id: String },
+    ParserError(#[serde(with = "ParserErrorDef")] ParserError),
+    ResolverError(#[serde(with = "ResolverErrorDef")] ResolverError),
+}
+
+fn no_op_function<'a>(_: &[FluentValue<'a>], _: &FluentArgs) -> FluentValue<'a> {
+    FluentValue::None
+}
+
+/// Rebuild a `FluentError::Overriding` by overriding an entry in a bundle,
+/// as `fluent_bundle` doesn't export the kind of the overridden entry.
+///
+/// Returns `None` if `id` isn't the identifier of a message or a term.
+fn overriding_error(kind: EntryKind, id: &str) -> Option<FluentError> {
+    let mut bundle = FluentBundle::new(vec![]);
+    let error = match kind {
+        EntryKind::Function => {
+            bundle.add_function(id, no_op_function).ok()?;
+            bundle.add_function(id, no_op_function).err()?
+        }
+        EntryKind::Message | EntryKind::Term => {
+            let prefix = if kind == EntryKind::Term { "-" } else { "" };
+            let source = format!("{}{} = Value\n", prefix, id);
+            let resource = Rc::new(FluentResource::try_new(source).ok()?);
+            bundle.add_resource(resource.clone()).ok()?;
+            bundle.add_resource(resource).err()?.into_iter().next()?
+        }
+    };
+    match &error {
+        FluentError::Overriding { id: found, .. } if found == id => Some(error),
+        _ => None,
+    }
+}
+
+pub(crate) mod fluent_error {
+    use super::*;
+
+    pub fn serialize<S>(error: &FluentError, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let def = match error.clone() {
+            FluentError::Overriding { kind, id } => FluentErrorDef::Overriding {
+                kind: EntryKind::from_fluent(&kind),
+                id,
+            },
+            FluentError::ParserError(error) => FluentErrorDef::ParserError(error),
+            FluentError::ResolverError(error) => FluentErrorDef::ResolverError(error),
+        };
+        def.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<FluentError, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match FluentErrorDef::deserialize(deserializer)? {
+            FluentErrorDef::Overriding { kind, id } => {
+                overriding_error(kind, &id).ok_or_else(|| {
+                    <D::Error as de::Error>::custom(format!("invalid {} id: {:?}", kind, id))
+                })?
+            }
+            FluentErrorDef::ParserError(error) => FluentError::ParserError(error),
+            FluentErrorDef::ResolverError(error) => FluentError::ResolverError(error),
+        })
+    }
+}
+
+pub(crate) mod io_error_kind {
+    use super::*;
+    use std::io::ErrorKind;
+
+    /// The kinds which are restored. The other ones, including the kinds
+    /// added to `std` since, become `Other`: as an `ErrorKind` can't be built
+    /// from an arbitrary name, their round trip is lossy.
+    const KINDS: &[ErrorKind] = &[
+        ErrorKind::NotFound,
+        ErrorKind::PermissionDenied,
+        ErrorKind::ConnectionRefused,
+        ErrorKind::ConnectionReset,
+        ErrorKind::ConnectionAborted,
+        ErrorKind::NotConnected,
+        ErrorKind::AddrInUse,
+        ErrorKind::AddrNotAvailable,
+        ErrorKind::BrokenPipe,
+        ErrorKind::AlreadyExists,
+        ErrorKind::WouldBlock,
+        ErrorKind::InvalidInput,
+        ErrorKind::InvalidData,
+        ErrorKind::TimedOut,
+        ErrorKind::WriteZero,
+        ErrorKind::Interrupted,
+        ErrorKind::UnexpectedEof,
+        ErrorKind::Other,
+    ];
+
+    pub fn serialize<S>(kind: &ErrorKind, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format!("{:?}", kind))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<ErrorKind, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        Ok(KINDS
+            .iter()
+            .find(|kind| format!("{:?}", kind) == name)
+            .copied()
+            .unwrap_or(ErrorKind::Other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{EntryKind, L10nRegistryError, L10nRegistrySetupError};
+    use crate::fluent::FluentResource;
+    use std::io;
+
+    fn roundtrip(error: L10nRegistryError) {
+        let json = serde_json::to_string(&error).unwrap();
+        let result: L10nRegistryError = serde_json::from_str(&json).unwrap();
+        assert_eq!(result, error);
+    }
+
+    #[test]
+    fn fluent_error_roundtrip() {
+        let (_, errors) = FluentResource::try_new("key = { $foo ->\n}\n".to_string()).unwrap_err();
+        roundtrip(L10nRegistryError::FluentError {
+            path: "browser/en-US/menu.ftl".to_string(),
+            loc: Some((1, 7)),
+            error: FluentError::ParserError(errors[0].clone()),
+        });
+
+        roundtrip(L10nRegistryError::FluentError {
+            path: "browser/en-US/menu.ftl".to_string(),
+            loc: None,
+            error: FluentError::ResolverError(ResolverError::Reference(ReferenceKind::Term {
+                id: "brand".to_string(),
+                attribute: Some("gender".to_string()),
+            })),
+        });
+
+        for (kind, id) in &[
+            (EntryKind::Message, "key"),
+            (EntryKind::Term, "brand"),
+            (EntryKind::Function, "NUMBER"),
+        ] {
+            roundtrip(L10nRegistryError::OverridingEntry {
+                source: "browser".to_string(),
+                path: "browser/en-US/menu.ftl".to_string(),
+                loc: Some((3, 1)),
+                original_path: None,
+                kind: *kind,
+                id: id.to_string(),
+            });
+        }
+    }
+
+    #[test]
+    fn overriding_fluent_error_roundtrip() {
+        let mut bundle = FluentBundle::new(vec![]);
+        let resource = || {
+            Rc::new(FluentResource::try_new("key = Value\n-brand = Firefox\n".to_string()).unwrap())
+        };
+        bundle.add_resource(resource()).unwrap();
+        let mut errors = bundle.add_resource(resource()).unwrap_err();
+        bundle.add_function("NUMBER", no_op_function).unwrap();
+        errors.push(bundle.add_function("NUMBER", no_op_function).unwrap_err());
+        assert_eq!(errors.len(), 3);
+
+        for error in errors {
+            roundtrip(L10nRegistryError::FluentError {
+                path: "browser/en-US/menu.ftl".to_string(),
+                loc: None,
+                error,
+            });
+        }
+
+        let json = r#"{"type":"FluentError","path":"menu.ftl","loc":null,"error":{"Overriding":{"kind":"message","id":"not an id"}}}"#;
+        assert!(serde_json::from_str::<L10nRegistryError>(json).is_err());
+    }
+
+    #[test]
+    fn io_error_roundtrip() {
+        roundtrip(L10nRegistryError::IoError {
+            path: "browser/en-US/menu.ftl".to_string(),
+            kind: io::ErrorKind::PermissionDenied,
+            message: "Permission denied".to_string(),
+        });
+
+        let json = r#"{"type":"IoError","path":"menu.ftl","kind":"Unknown","message":""}"#;
+        let result: L10nRegistryError = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            result,
+            L10nRegistryError::IoError {
+                kind: io::ErrorKind::Other,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn setup_error_roundtrip() {
+        for error in [
+            L10nRegistrySetupError::RegistryLocked,
+            L10nRegistrySetupError::DuplicatedSource {
+                name: "browser".to_string(),
+            },
+        ] {
+            let json = serde_json::to_string(&error).unwrap();
+            assert_eq!(
+                serde_json::from_str::<L10nRegistrySetupError>(&json).unwrap(),
+                error
+            );
+        }
+        assert_eq!(
+            serde_json::to_string(&L10nRegistrySetupError::RegistryLocked).unwrap(),
+            r#"{"type":"RegistryLocked"}"#
+        );
+    }
+}
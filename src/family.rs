@@ -0,0 +1,291 @@
+//! The pointer and lock types the sources and the registries are built on.
+//!
+//! [`FileSource`] and [`L10nRegistry`] are generic over a [`Family`], which
+//! is [`Local`] by default: they are built on `Rc` and `RefCell`, and stay on
+//! the thread they have been created on. With the `sync` feature, the
+//! `Concurrent` family builds them on `Arc` and `RwLock` instead, so that they
+//! can be shared across threads.
+//!
+//! [`FileSource`]: ../source/struct.FileSource.html
+//! [`L10nRegistry`]: ../registry/struct.L10nRegistry.html
+//! [`Family`]: trait.Family.html
+//! [`Local`]: enum.Local.html
+
+use crate::env::ErrorReporter;
+use crate::fluent::{FluentError, FluentResource};
+use crate::registry::ResourceChange;
+use crate::source::{FileFetcher, FileSource};
+
+use std::{
+    cell::{Cell, Ref, RefCell, RefMut},
+    future::Future,
+    io,
+    pin::Pin,
+    rc::Rc,
+};
+
+use fluent_bundle::{bundle, memoizer::MemoizerKind};
+use futures::{channel::oneshot, future::Shared, FutureExt};
+use intl_memoizer::IntlLangMemoizer;
+use unic_langid::LanguageIdentifier;
+
+/// The family of pointer and lock types a [`FileSource`] or an
+/// [`L10nRegistry`] is built on.
+///
+/// The trait is sealed: the families are [`Local`] and, with the `sync`
+/// feature, `Concurrent`.
+///
+/// [`FileSource`]: ../source/struct.FileSource.html
+/// [`L10nRegistry`]: ../registry/struct.L10nRegistry.html
+/// [`Local`]: enum.Local.html
+pub trait Family: private::Sealed + Sized + 'static {
+    /// A reference counted pointer, such as `Rc`.
+    type Ptr<T>: private::Pointer<T>;
+    /// A cell allowing to mutate a shared value, such as `RefCell`.
+    type Lock<T>: private::Lock<T>;
+    /// A shared counter, such as `Cell<usize>`.
+    type Counter: private::Counter;
+    /// The fetcher of a source, such as `dyn FileFetcher`.
+    type Fetcher: ?Sized + private::Fetcher;
+    /// The error reporter of a source, such as `dyn ErrorReporter`.
+    type Reporter: ?Sized + ErrorReporter;
+    /// A listener of the changes of the resources of a registry.
+    type Listener: private::Listener;
+    /// The memoizer of the bundles generated by a registry.
+    type Memoizer: private::BundleMemoizer;
+    /// A load of a resource in progress, shared by all the requests of the
+    /// resource.
+    type ResourceFuture: Future<Output = Option<Self::Ptr<FluentResource>>> + Clone + Unpin;
+}
+
+/// The resources of the sources of the family `F`.
+pub type Resource<F> = <F as Family>::Ptr<FluentResource>;
+
+/// The bundles generated by the registries of the family `F`.
+pub type Bundle<F> = bundle::FluentBundle<Resource<F>, <F as Family>::Memoizer>;
+
+/// A bundle generated by the registries of the family `F`, along with the
+/// errors encountered while adding its resources, if any.
+pub type BundleResult<F> = Result<Bundle<F>, (Bundle<F>, Vec<FluentError>)>;
+
+/// The family of the sources and the registries which stay on the thread
+/// they have been created on.
+#[derive(Debug, Clone, Copy)]
+pub enum Local {}
+
+impl Family for Local {
+    type Ptr<T> = Rc<T>;
+    type Lock<T> = RefCell<T>;
+    type Counter = Cell<usize>;
+    type Fetcher = dyn FileFetcher;
+    type Reporter = dyn ErrorReporter;
+    type Listener = Rc<dyn Fn(&[ResourceChange])>;
+    type Memoizer = IntlLangMemoizer;
+    type ResourceFuture = Shared<Pin<Box<dyn Future<Output = Option<Rc<FluentResource>>>>>>;
+}
+
+impl private::Sealed for Local {
+    fn load_resource(
+        source: &FileSource<Self>,
+        path: String,
+        load_id: usize,
+        sync_result: oneshot::Receiver<Option<Rc<FluentResource>>>,
+    ) -> <Self as Family>::ResourceFuture {
+        source
+            .load_resource(path, load_id, sync_result)
+            .boxed_local()
+            .shared()
+    }
+}
+
+/// Fetchers the sources of the family `F` can be created with: the
+/// [`FileFetcher`](../source/trait.FileFetcher.html)s for [`Local`], and the
+/// `SendFileFetcher`s for `Concurrent`.
+///
+/// [`Local`]: enum.Local.html
+pub trait IntoFetcher<F: Family> {
+    #[doc(hidden)]
+    fn into_fetcher(self) -> Box<F::Fetcher>;
+}
+
+impl<T: FileFetcher + 'static> IntoFetcher<Local> for T {
+    fn into_fetcher(self) -> Box<dyn FileFetcher> {
+        Box::new(self)
+    }
+}
+
+/// Error reporters the sources of the family `F` can report errors to. The
+/// reporters of the `Concurrent` sources must be `Send` and `Sync`.
+pub trait IntoReporter<F: Family> {
+    #[doc(hidden)]
+    fn into_reporter(self) -> Box<F::Reporter>;
+}
+
+impl<T: ErrorReporter + 'static> IntoReporter<Local> for T {
+    fn into_reporter(self) -> Box<dyn ErrorReporter> {
+        Box::new(self)
+    }
+}
+
+/// Change listeners the registries of the family `F` accept. The listeners
+/// of the `Concurrent` registries must be `Send` and `Sync`.
+pub trait IntoListener<F: Family> {
+    #[doc(hidden)]
+    fn into_listener(self) -> F::Listener;
+}
+
+impl<T: Fn(&[ResourceChange]) + 'static> IntoListener<Local> for T {
+    fn into_listener(self) -> Rc<dyn Fn(&[ResourceChange])> {
+        Rc::new(self)
+    }
+}
+
+pub(crate) mod private {
+    use super::*;
+
+    use std::ops::{Deref, DerefMut};
+
+    pub trait Sealed: Sized {
+        /// Start loading the resource at `path` for `source`, resolving with
+        /// the result of a sync load of the same resource if it's received
+        /// through `sync_result` first.
+        fn load_resource(
+            source: &FileSource<Self>,
+            path: String,
+            load_id: usize,
+            sync_result: oneshot::Receiver<Option<Resource<Self>>>,
+        ) -> Self::ResourceFuture
+        where
+            Self: Family;
+    }
+
+    pub trait Pointer<T>:
+        Clone + Deref<Target = T> + AsRef<T> + std::borrow::Borrow<T> + Unpin
+    {
+        fn new(value: T) -> Self;
+
+        fn get_mut(this: &mut Self) -> Option<&mut T>;
+    }
+
+    impl<T> Pointer<T> for Rc<T> {
+        fn new(value: T) -> Self {
+            Rc::new(value)
+        }
+
+        fn get_mut(this: &mut Self) -> Option<&mut T> {
+            Rc::get_mut(this)
+        }
+    }
+
+    pub trait Lock<T> {
+        type ReadGuard<'a>: Deref<Target = T>
+        where
+            Self: 'a;
+        type WriteGuard<'a>: DerefMut<Target = T>
+        where
+            Self: 'a;
+
+        fn new(value: T) -> Self;
+
+        fn read(&self) -> Self::ReadGuard<'_>;
+
+        fn write(&self) -> Self::WriteGuard<'_>;
+
+        /// Returns `None` if the value is being written to by the current
+        /// thread, or if a thread has panicked while writing to it.
+        fn try_read(&self) -> Option<Self::ReadGuard<'_>>;
+
+        /// Returns `None` if the value is being read or written to by the
+        /// current thread, or if a thread has panicked while writing to it.
+        fn try_write(&self) -> Option<Self::WriteGuard<'_>>;
+    }
+
+    impl<T> Lock<T> for RefCell<T> {
+        type ReadGuard<'a>
+            = Ref<'a, T>
+        where
+            T: 'a;
+        type WriteGuard<'a>
+            = RefMut<'a, T>
+        where
+            T: 'a;
+
+        fn new(value: T) -> Self {
+            RefCell::new(value)
+        }
+
+        fn read(&self) -> Ref<'_, T> {
+            self.borrow()
+        }
+
+        fn write(&self) -> RefMut<'_, T> {
+            self.borrow_mut()
+        }
+
+        fn try_read(&self) -> Option<Ref<'_, T>> {
+            self.try_borrow().ok()
+        }
+
+        fn try_write(&self) -> Option<RefMut<'_, T>> {
+            self.try_borrow_mut().ok()
+        }
+    }
+
+    pub trait Counter: Default {
+        fn get(&self) -> usize;
+
+        fn set(&self, value: usize);
+    }
+
+    impl Counter for Cell<usize> {
+        fn get(&self) -> usize {
+            Cell::get(self)
+        }
+
+        fn set(&self, value: usize) {
+            Cell::set(self, value)
+        }
+    }
+
+    pub trait Fetcher {
+        type Future<'a>: Future<Output = io::Result<String>> + 'a
+        where
+            Self: 'a;
+
+        fn fetch_sync(&self, path: &str) -> io::Result<String>;
+
+        fn fetch<'a>(&'a self, path: &'a str) -> Self::Future<'a>;
+    }
+
+    impl Fetcher for dyn FileFetcher {
+        type Future<'a> = Pin<Box<dyn Future<Output = io::Result<String>> + 'a>>;
+
+        fn fetch_sync(&self, path: &str) -> io::Result<String> {
+            FileFetcher::fetch_sync(self, path)
+        }
+
+        fn fetch<'a>(&'a self, path: &'a str) -> Self::Future<'a> {
+            FileFetcher::fetch(self, path)
+        }
+    }
+
+    pub trait Listener: Clone {
+        fn call(&self, changes: &[ResourceChange]);
+    }
+
+    impl Listener for Rc<dyn Fn(&[ResourceChange])> {
+        fn call(&self, changes: &[ResourceChange]) {
+            self(changes)
+        }
+    }
+
+    pub trait BundleMemoizer: MemoizerKind + Sized {
+        fn new_bundle<R>(locales: Vec<LanguageIdentifier>) -> bundle::FluentBundle<R, Self>;
+    }
+
+    impl BundleMemoizer for IntlLangMemoizer {
+        fn new_bundle<R>(locales: Vec<LanguageIdentifier>) -> bundle::FluentBundle<R, Self> {
+            bundle::FluentBundle::new(locales)
+        }
+    }
+}
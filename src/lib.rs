@@ -1,8 +1,13 @@
 pub mod env;
 pub mod errors;
+pub mod family;
 pub mod fluent;
 pub mod registry;
 pub mod solver;
 pub mod source;
+#[cfg(feature = "sync")]
+pub mod sync;
 #[cfg(feature = "fluent-testing")]
 pub mod testing;
+#[cfg(feature = "watcher")]
+pub mod watcher;
@@ -3,65 +3,72 @@ use std::{
     task::{Context, Poll},
 };
 
-use super::{BundleAdapter, L10nRegistry, L10nRegistryLocked};
-use crate::solver::{AsyncTester, ParallelProblemSolver};
+use super::{
+    availability::PendingAvailability, BundleAdapter, BundleMetadata, L10nRegistry,
+    L10nRegistryLocked, ResourceId, WithMetadata,
+};
+use crate::solver::{AsyncTester, ParallelProblemSolver, SolverOptions};
 use crate::{
     env::ErrorReporter,
-    errors::L10nRegistryError,
-    fluent::{FluentBundle, FluentError},
+    family::{BundleResult, Family, Local},
     source::{ResourceOption, ResourceStatus},
 };
 
 use fluent_fallback::generator::BundleStream;
 use futures::{
+    future::poll_fn,
+    ready,
     stream::{Collect, FuturesOrdered},
     Stream, StreamExt,
 };
 use std::future::Future;
 use unic_langid::LanguageIdentifier;
 
-impl<'a, B> L10nRegistryLocked<'a, B> {}
+impl<'a, B, F: Family> L10nRegistryLocked<'a, B, F> {}
 
-impl<P, B> L10nRegistry<P, B>
+impl<P, B, F: Family> L10nRegistry<P, B, F>
 where
     P: Clone,
     B: Clone,
 {
-    pub fn generate_bundles_for_lang(
+    pub fn generate_bundles_for_lang<R: Into<ResourceId>>(
         &self,
         langid: LanguageIdentifier,
-        resource_ids: Vec<String>,
-    ) -> GenerateBundles<P, B> {
-        let lang_ids = vec![langid];
+        resource_ids: Vec<R>,
+    ) -> GenerateBundles<P, B, F> {
+        let lang_ids = self.expand_locales(vec![langid]);
 
         GenerateBundles::new(self.clone(), lang_ids.into_iter(), resource_ids)
     }
 
-    pub fn generate_bundles(
+    pub fn generate_bundles<R: Into<ResourceId>>(
         &self,
         locales: std::vec::IntoIter<LanguageIdentifier>,
-        resource_ids: Vec<String>,
-    ) -> GenerateBundles<P, B> {
-        GenerateBundles::new(self.clone(), locales, resource_ids)
+        resource_ids: Vec<R>,
+    ) -> GenerateBundles<P, B, F> {
+        let locales = self.expand_locales(locales);
+        GenerateBundles::new(self.clone(), locales.into_iter(), resource_ids)
     }
 }
 
-enum State<P, B> {
+enum State<P, B, F: Family> {
     Empty,
     Locale(LanguageIdentifier),
     Solver {
         locale: LanguageIdentifier,
-        solver: ParallelProblemSolver<GenerateBundles<P, B>>,
+        solver: ParallelProblemSolver<GenerateBundles<P, B, F>>,
     },
 }
 
-impl<P, B> Default for State<P, B> {
+// Deriving `Default` would require `P`, `B` and `F` to implement it.
+#[allow(clippy::derivable_impls)]
+impl<P, B, F: Family> Default for State<P, B, F> {
     fn default() -> Self {
         Self::Empty
     }
 }
 
-impl<P, B> State<P, B> {
+impl<P, B, F: Family> State<P, B, F> {
     fn get_locale(&self) -> &LanguageIdentifier {
         match self {
             Self::Locale(locale) => locale,
@@ -70,14 +77,14 @@ impl<P, B> State<P, B> {
         }
     }
 
-    fn take_solver(&mut self) -> ParallelProblemSolver<GenerateBundles<P, B>> {
+    fn take_solver(&mut self) -> ParallelProblemSolver<GenerateBundles<P, B, F>> {
         replace_with::replace_with_or_default_and_return(self, |self_| match self_ {
             Self::Solver { locale, solver } => (solver, Self::Locale(locale)),
             _ => unreachable!(),
         })
     }
 
-    fn put_back_solver(&mut self, solver: ParallelProblemSolver<GenerateBundles<P, B>>) {
+    fn put_back_solver(&mut self, solver: ParallelProblemSolver<GenerateBundles<P, B, F>>) {
         replace_with::replace_with_or_default(self, |self_| match self_ {
             Self::Locale(locale) => Self::Solver { locale, solver },
             _ => unreachable!(),
@@ -85,45 +92,104 @@ impl<P, B> State<P, B> {
     }
 }
 
-pub struct GenerateBundles<P, B> {
-    reg: L10nRegistry<P, B>,
+pub struct GenerateBundles<P, B, F: Family = Local> {
+    reg: L10nRegistry<P, B, F>,
     locales: std::vec::IntoIter<LanguageIdentifier>,
-    res_ids: Vec<String>,
-    state: State<P, B>,
+    res_ids: Vec<ResourceId>,
+    solver_options: SolverOptions,
+    state: State<P, B, F>,
+    cache_generation: usize,
+    with_metadata: bool,
+    metadata: Option<BundleMetadata>,
 }
 
-impl<P, B> GenerateBundles<P, B> {
-    fn new(
-        reg: L10nRegistry<P, B>,
+impl<P, B, F: Family> GenerateBundles<P, B, F> {
+    fn new<R: Into<ResourceId>>(
+        reg: L10nRegistry<P, B, F>,
         locales: std::vec::IntoIter<LanguageIdentifier>,
-        res_ids: Vec<String>,
+        res_ids: Vec<R>,
     ) -> Self {
+        let solver_options = reg.shared.solver_options;
         Self {
             reg,
             locales,
-            res_ids,
+            res_ids: res_ids.into_iter().map(Into::into).collect(),
+            solver_options,
             state: State::Empty,
+            cache_generation: 0,
+            with_metadata: false,
+            metadata: None,
+        }
+    }
+
+    /// Yield the [`BundleMetadata`](struct.BundleMetadata.html) of each
+    /// bundle along with the bundle.
+    pub fn with_metadata(mut self) -> WithMetadata<Self> {
+        self.with_metadata = true;
+        WithMetadata(self)
+    }
+
+    /// Limit the solutions yielded for each locale, overriding the options
+    /// set with `L10nRegistry::set_solver_options`.
+    pub fn with_solver_options(mut self, solver_options: SolverOptions) -> Self {
+        self.solver_options = solver_options;
+        self
+    }
+
+    fn new_solver(&mut self, locale: &LanguageIdentifier) -> ParallelProblemSolver<Self> {
+        let lock = self.reg.lock();
+        self.cache_generation = lock.cache_generation();
+        let mut solver = ParallelProblemSolver::new(self.res_ids.len(), lock.len());
+        solver.set_options(self.solver_options);
+        for (res_idx, res_id) in self.res_ids.iter().enumerate() {
+            if res_id.is_optional() {
+                solver.set_optional(res_idx);
+            }
         }
+        self.reg
+            .prefill_solver(&lock, locale, &self.res_ids, &mut solver);
+        solver
+    }
+
+    /// Take the solver out of the state, discarding the results of its tests
+    /// if the cache of any of the sources has been invalidated since.
+    fn take_solver(&mut self) -> ParallelProblemSolver<Self> {
+        let mut solver = self.state.take_solver();
+        let cache_generation = self.reg.lock().cache_generation();
+        if cache_generation != self.cache_generation {
+            solver.invalidate_cache();
+            self.cache_generation = cache_generation;
+        }
+        solver
     }
 }
 
-pub type ResourceSetStream = Collect<FuturesOrdered<ResourceStatus>, Vec<ResourceOption>>;
-pub struct TestResult(ResourceSetStream);
-impl std::marker::Unpin for TestResult {}
+pub type ResourceSetStream<F = Local> =
+    Collect<FuturesOrdered<ResourceStatus<F>>, Vec<ResourceOption<F>>>;
+pub struct TestResult<F: Family = Local> {
+    stream: ResourceSetStream<F>,
+    pending: Option<PendingAvailability<F>>,
+}
+impl<F: Family> std::marker::Unpin for TestResult<F> {}
 
-impl Future for TestResult {
+impl<F: Family> Future for TestResult<F> {
     type Output = Vec<bool>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let pinned = Pin::new(&mut self.0);
-        pinned
-            .poll(cx)
-            .map(|set| set.iter().map(|c| c.is_some()).collect())
+        let pinned = Pin::new(&mut self.stream);
+        let set: Vec<bool> = ready!(pinned.poll(cx))
+            .iter()
+            .map(|c| c.is_some())
+            .collect();
+        if let Some(pending) = self.pending.take() {
+            pending.record(&set);
+        }
+        set.into()
     }
 }
 
-impl<'l, P, B> AsyncTester for GenerateBundles<P, B> {
-    type Result = TestResult;
+impl<P, B, F: Family> AsyncTester for GenerateBundles<P, B, F> {
+    type Result = TestResult<F>;
 
     fn test_async(&self, query: Vec<(usize, usize)>) -> Self::Result {
         let locale = self.state.get_locale();
@@ -132,43 +198,91 @@ impl<'l, P, B> AsyncTester for GenerateBundles<P, B> {
         let stream = query
             .iter()
             .map(|(res_idx, source_idx)| {
-                let res = &self.res_ids[*res_idx];
+                let res = &self.res_ids[*res_idx].value;
                 lock.source_idx(*source_idx).fetch_file(locale, res)
             })
             .collect::<FuturesOrdered<_>>();
-        TestResult(stream.collect())
+        let cells = query
+            .iter()
+            .map(|(res_idx, source_idx)| {
+                (
+                    self.res_ids[*res_idx].value.clone(),
+                    lock.source_idx(*source_idx).name.clone(),
+                )
+            })
+            .collect();
+        TestResult {
+            stream: stream.collect(),
+            pending: Some(self.reg.pending_availability(&lock, locale, cells)),
+        }
     }
 }
 
 #[async_trait::async_trait(?Send)]
-impl<P, B> BundleStream for GenerateBundles<P, B> {
+impl<P, B, F> BundleStream for GenerateBundles<P, B, F>
+where
+    P: ErrorReporter,
+    F: Family,
+{
     async fn prefetch_async(&mut self) {
-        todo!();
+        let mut solver = if let State::Solver { .. } = self.state {
+            self.take_solver()
+        } else if let Some(locale) = self.locales.next() {
+            let solver = self.new_solver(&locale);
+            self.state = State::Locale(locale);
+            solver
+        } else {
+            return;
+        };
+
+        let tester: &Self = self;
+        let result = poll_fn(|cx| Pin::new(&mut solver).try_poll_next(cx, tester, true)).await;
+
+        if let Err(idx) = result {
+            let error = self
+                .reg
+                .lock()
+                .missing_resource(self.state.get_locale(), &self.res_ids[idx].value);
+            self.reg.shared.provider.report_errors(vec![error]);
+        }
+        self.state.put_back_solver(solver);
     }
 }
 
-impl<P, B> Stream for GenerateBundles<P, B>
+impl<P, B, F> Stream for GenerateBundles<P, B, F>
 where
     P: ErrorReporter,
-    B: BundleAdapter,
+    B: BundleAdapter<F>,
+    F: Family,
 {
-    type Item = Result<FluentBundle, (FluentBundle, Vec<FluentError>)>;
+    type Item = BundleResult<F>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         loop {
             if let State::Solver { .. } = self.state {
-                let mut solver = self.state.take_solver();
+                let mut solver = self.take_solver();
                 let pinned_solver = Pin::new(&mut solver);
                 match pinned_solver.try_poll_next(cx, &self, false) {
                     std::task::Poll::Ready(order) => match order {
                         Ok(Some(order)) => {
                             let locale = self.state.get_locale();
-                            let bundle = self.reg.lock().bundle_from_order(
+                            let lock = self.reg.lock();
+                            let bundle = lock.bundle_from_order(
                                 locale.clone(),
                                 &order,
                                 &self.res_ids,
                                 &self.reg.shared.provider,
                             );
+                            let metadata = if self.with_metadata && bundle.is_some() {
+                                Some(lock.bundle_metadata(locale, &order, &self.res_ids))
+                            } else {
+                                None
+                            };
+                            drop(lock);
+                            self.metadata = metadata;
+                            if bundle.is_none() {
+                                solver.discard_solution();
+                            }
                             self.state.put_back_solver(solver);
                             if bundle.is_some() {
                                 return bundle.into();
@@ -181,12 +295,11 @@ where
                             continue;
                         }
                         Err(idx) => {
-                            self.reg.shared.provider.report_errors(vec![
-                                L10nRegistryError::MissingResource {
-                                    locale: self.state.get_locale().clone(),
-                                    res_id: self.res_ids[idx].clone(),
-                                },
-                            ]);
+                            let error = self.reg.lock().missing_resource(
+                                self.state.get_locale(),
+                                &self.res_ids[idx].value,
+                            );
+                            self.reg.shared.provider.report_errors(vec![error]);
                             self.state = State::Empty;
                             continue;
                         }
@@ -197,7 +310,7 @@ where
                     }
                 }
             } else if let Some(locale) = self.locales.next() {
-                let solver = ParallelProblemSolver::new(self.res_ids.len(), self.reg.lock().len());
+                let solver = self.new_solver(&locale);
                 self.state = State::Solver { locale, solver };
             } else {
                 return None.into();
@@ -205,3 +318,24 @@ where
         }
     }
 }
+
+impl<P, B, F> Stream for WithMetadata<GenerateBundles<P, B, F>>
+where
+    P: ErrorReporter,
+    B: BundleAdapter<F>,
+    F: Family,
+{
+    type Item = (BundleResult<F>, BundleMetadata);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let inner = &mut self.0;
+        match Pin::new(&mut *inner).poll_next(cx) {
+            Poll::Ready(Some(bundle)) => {
+                let metadata = inner.metadata.take().expect("Missing bundle metadata");
+                Poll::Ready(Some((bundle, metadata)))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use super::{L10nRegistry, L10nRegistryLocked, ResourceId};
+use crate::family::{
+    private::{Counter, Lock},
+    Family,
+};
+use crate::solver::ProblemSolver;
+use crate::source::next_generation;
+
+use rustc_hash::FxHashMap;
+use std::time::{Duration, Instant};
+use unic_langid::LanguageIdentifier;
+
+/// Whether each source of a registry provides a resource for a locale, as
+/// found by the bundle generators.
+///
+/// The matrix is shared by all the generators of a registry, so that the
+/// solvers of a new generator, or of the next locale, start from the results
+/// of the previous ones instead of testing the sources again. It's tied to
+/// the cache generation of the sources, and emptied when any of them is
+/// invalidated, restored, registered, updated or removed. A resource found
+/// missing in a source is forgotten once the `missing_ttl` of the source has
+/// passed, like in the cache of the source.
+#[derive(Debug, Default)]
+pub(crate) struct AvailabilityMatrix {
+    generation: usize,
+    locales: FxHashMap<LanguageIdentifier, FxHashMap<String, FxHashMap<String, Availability>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Availability {
+    available: bool,
+    /// When the availability has first been found.
+    found: Instant,
+}
+
+impl AvailabilityMatrix {
+    /// Empty the matrix if it has been filled for another cache generation
+    /// of the sources.
+    pub fn sync_generation(&mut self, generation: usize) {
+        if generation != self.generation {
+            self.clear();
+            self.generation = generation;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.locales.clear();
+    }
+
+    /// Whether `source` provides `res_id` for `locale`, if it's known. The
+    /// resource is forgotten if it has been found missing at least
+    /// `missing_ttl` ago.
+    pub fn get(
+        &mut self,
+        locale: &LanguageIdentifier,
+        res_id: &str,
+        source: &str,
+        missing_ttl: Option<Duration>,
+    ) -> Option<bool> {
+        let sources = self.locales.get_mut(locale)?.get_mut(res_id)?;
+        let cell = *sources.get(source)?;
+        if !cell.available && missing_ttl.is_some_and(|ttl| cell.found.elapsed() >= ttl) {
+            sources.remove(source);
+            return None;
+        }
+        Some(cell.available)
+    }
+
+    /// Record whether `source` provides `res_id` for `locale`, unless the
+    /// result has been found in another `generation` than the matrix.
+    pub fn insert(
+        &mut self,
+        generation: usize,
+        locale: &LanguageIdentifier,
+        res_id: &str,
+        source: &str,
+        available: bool,
+    ) {
+        if generation != self.generation {
+            return;
+        }
+        let sources = self
+            .locales
+            .entry(locale.clone())
+            .or_default()
+            .entry(res_id.to_string())
+            .or_default();
+        match sources.get(source) {
+            Some(cell) if cell.available == available => {}
+            _ => {
+                sources.insert(
+                    source.to_string(),
+                    Availability {
+                        available,
+                        found: Instant::now(),
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// The cells of an asynchronous test, to be recorded in the availability
+/// matrix once resolved.
+pub(crate) struct PendingAvailability<F: Family> {
+    matrix: F::Ptr<F::Lock<AvailabilityMatrix>>,
+    generation: usize,
+    locale: LanguageIdentifier,
+    /// The res_id and the source name of each tested cell.
+    cells: Vec<(String, String)>,
+}
+
+impl<F: Family> PendingAvailability<F> {
+    pub fn record(self, results: &[bool]) {
+        let mut matrix = self.matrix.write();
+        for ((res_id, source), available) in self.cells.iter().zip(results) {
+            matrix.insert(self.generation, &self.locale, res_id, source, *available);
+        }
+    }
+}
+
+impl<P, B, F: Family> L10nRegistry<P, B, F> {
+    /// Whether the bundle generators have found `source` to provide `res_id`
+    /// for `locale`, or `None` if it hasn't been tested since the sources
+    /// have last changed.
+    pub fn known_availability(
+        &self,
+        locale: &LanguageIdentifier,
+        res_id: &str,
+        source: &str,
+    ) -> Option<bool> {
+        let lock = self.lock();
+        let missing_ttl = lock.get_source(source)?.missing_ttl();
+        let mut matrix = self.shared.availability.write();
+        matrix.sync_generation(lock.cache_generation());
+        matrix.get(locale, res_id, source, missing_ttl)
+    }
+
+    /// Fill the cache of `solver` with the known availability of `res_ids`
+    /// for `locale` in the sources of `lock`.
+    pub(crate) fn prefill_solver(
+        &self,
+        lock: &L10nRegistryLocked<'_, B, F>,
+        locale: &LanguageIdentifier,
+        res_ids: &[ResourceId],
+        solver: &mut ProblemSolver,
+    ) {
+        let mut matrix = self.shared.availability.write();
+        matrix.sync_generation(lock.cache_generation());
+        for source_idx in 0..lock.len() {
+            let source = lock.source_idx(source_idx);
+            let missing_ttl = source.missing_ttl();
+            for (res_idx, res_id) in res_ids.iter().enumerate() {
+                if let Some(available) =
+                    matrix.get(locale, &res_id.value, &source.name, missing_ttl)
+                {
+                    solver.set_cell(res_idx, source_idx, available);
+                }
+            }
+        }
+    }
+
+    /// Record the result of testing `source` for `res_id`, found in the
+    /// cache `generation` of the sources.
+    pub(crate) fn record_availability(
+        &self,
+        generation: usize,
+        locale: &LanguageIdentifier,
+        res_id: &str,
+        source: &str,
+        available: bool,
+    ) {
+        self.shared
+            .availability
+            .write()
+            .insert(generation, locale, res_id, source, available);
+    }
+
+    /// Prepare the recording of the results of testing `cells`, as pairs of
+    /// res_id and source name, in the sources of `lock`.
+    pub(crate) fn pending_availability(
+        &self,
+        lock: &L10nRegistryLocked<'_, B, F>,
+        locale: &LanguageIdentifier,
+        cells: Vec<(String, String)>,
+    ) -> PendingAvailability<F> {
+        let generation = lock.cache_generation();
+        self.shared.availability.write().sync_generation(generation);
+        PendingAvailability {
+            matrix: self.shared.availability.clone(),
+            generation,
+            locale: locale.clone(),
+            cells,
+        }
+    }
+
+    /// Forget the availability of all the resources and start a new
+    /// generation, after the sources have been changed.
+    pub(crate) fn clear_availability(&self) {
+        self.shared.generation.set(next_generation());
+        self.shared.availability.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn availability_matrix_generation() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let mut matrix = AvailabilityMatrix::default();
+
+        matrix.insert(0, &en_us, "main.ftl", "app", true);
+        matrix.insert(0, &en_us, "menu.ftl", "app", false);
+        assert_eq!(matrix.get(&en_us, "main.ftl", "app", None), Some(true));
+        assert_eq!(matrix.get(&en_us, "menu.ftl", "app", None), Some(false));
+        assert_eq!(matrix.get(&en_us, "main.ftl", "toolkit", None), None);
+
+        matrix.sync_generation(1);
+        assert_eq!(matrix.get(&en_us, "main.ftl", "app", None), None);
+
+        // A result found before the sources changed is outdated.
+        matrix.insert(0, &en_us, "main.ftl", "app", true);
+        assert_eq!(matrix.get(&en_us, "main.ftl", "app", None), None);
+    }
+
+    #[test]
+    fn availability_matrix_missing_ttl() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let mut matrix = AvailabilityMatrix::default();
+        let ttl = Some(Duration::from_secs(0));
+
+        matrix.insert(0, &en_us, "main.ftl", "app", true);
+        matrix.insert(0, &en_us, "menu.ftl", "app", false);
+        matrix.insert(0, &en_us, "menu.ftl", "toolkit", false);
+        assert_eq!(matrix.get(&en_us, "main.ftl", "app", ttl), Some(true));
+        assert_eq!(matrix.get(&en_us, "menu.ftl", "app", ttl), None);
+
+        // Only the expired cell is forgotten.
+        assert_eq!(matrix.get(&en_us, "menu.ftl", "app", None), None);
+        assert_eq!(matrix.get(&en_us, "menu.ftl", "toolkit", None), Some(false));
+    }
+}
@@ -0,0 +1,162 @@
+use super::{L10nRegistry, LocaleFallback};
+use crate::env::ErrorReporter;
+use crate::errors::L10nRegistrySetupError;
+use crate::family::{private::Lock, Family, IntoFetcher, IntoReporter};
+use crate::solver::SolverOptions;
+use crate::source::{FileSource, FileSourceDescription};
+
+/// A declarative description of the sources and the settings of an
+/// [`L10nRegistry`], loaded with [`L10nRegistry::from_config`].
+///
+/// Reading and writing a configuration requires the `serde` feature, which
+/// adds [`from_json`] and [`to_json`] and makes the configuration readable
+/// from any format supported by serde. Without it, the configuration can
+/// only be built in code. All the fields may be omitted. For example, in
+/// JSON:
+///
+/// ```json
+/// {
+///   "sources": [
+///     {
+///       "name": "browser",
+///       "locales": ["en-US", "pl"],
+///       "pre_path": "browser/{locale}/"
+///     },
+///     {
+///       "name": "langpack-de",
+///       "locales": ["de"],
+///       "pre_path": "langpacks/de/",
+///       "index": { "de": ["menu.ftl"] },
+///       "options": { "priority": 1 }
+///     }
+///   ],
+///   "locale_fallback": { "default_locale": "en-US" }
+/// }
+/// ```
+///
+/// [`L10nRegistry`]: struct.L10nRegistry.html
+/// [`L10nRegistry::from_config`]: struct.L10nRegistry.html#method.from_config
+/// [`from_json`]: #method.from_json
+/// [`to_json`]: #method.to_json
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RegistryConfig {
+    /// The sources, registered in order.
+    pub sources: Vec<FileSourceDescription>,
+    pub locale_fallback: Option<LocaleFallback>,
+    pub solver_options: SolverOptions,
+}
+
+#[cfg(feature = "serde")]
+impl RegistryConfig {
+    /// Parse a configuration from JSON. Requires the `serde` feature.
+    pub fn from_json(source: &str) -> Result<Self, L10nRegistrySetupError> {
+        serde_json::from_str(source).map_err(|err| L10nRegistrySetupError::InvalidConfig {
+            message: err.to_string(),
+        })
+    }
+
+    /// Serialize the configuration as pretty-printed JSON. Requires the
+    /// `serde` feature.
+    pub fn to_json(&self) -> Result<String, L10nRegistrySetupError> {
+        serde_json::to_string_pretty(self).map_err(|err| L10nRegistrySetupError::InvalidConfig {
+            message: err.to_string(),
+        })
+    }
+}
+
+impl<P, B, F: Family> L10nRegistry<P, B, F> {
+    /// Build a registry reporting errors to `provider`, with the sources and
+    /// the settings of `config`.
+    ///
+    /// The `fetcher_factory` creates the fetcher of each source from its
+    /// description, and may, for example, pick a fetcher based on the
+    /// `pre_path`.
+    pub fn from_config<C, T>(
+        provider: P,
+        config: RegistryConfig,
+        mut fetcher_factory: C,
+    ) -> Result<Self, L10nRegistrySetupError>
+    where
+        P: ErrorReporter + IntoReporter<F> + Clone,
+        C: FnMut(&FileSourceDescription) -> T,
+        T: IntoFetcher<F>,
+    {
+        let mut reg = Self::with_provider(provider.clone());
+        if let Some(locale_fallback) = config.locale_fallback {
+            reg.set_locale_fallback(locale_fallback)?;
+        }
+        reg.set_solver_options(config.solver_options)?;
+
+        let mut sources = vec![];
+        for description in config.sources {
+            let fetcher = fetcher_factory(&description);
+            let mut source = FileSource::from_description(description, fetcher);
+            source.set_reporter(provider.clone())?;
+            sources.push(source);
+        }
+        reg.register_sources(sources)?;
+        Ok(reg)
+    }
+
+    /// Describe the sources and the settings of the registry, so that it can
+    /// be built again with [`from_config`](#method.from_config).
+    pub fn get_config(&self) -> Result<RegistryConfig, L10nRegistrySetupError> {
+        let sources = self
+            .shared
+            .sources
+            .try_read()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        Ok(RegistryConfig {
+            sources: sources.iter().map(FileSource::describe).collect(),
+            locale_fallback: self.shared.locale_fallback.clone(),
+            solver_options: self.shared.solver_options,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::solver::Lookahead;
+    use unic_langid::LanguageIdentifier;
+
+    #[test]
+    fn registry_config_json() {
+        let config = RegistryConfig::from_json(
+            r#"{
+                "sources": [
+                    {"name": "browser", "locales": ["en-US"], "pre_path": "browser/{locale}/"}
+                ],
+                "locale_fallback": {"default_locale": "en-US"},
+                "solver_options": {"greedy": true, "lookahead": {"Candidates": 2}}
+            }"#,
+        )
+        .unwrap();
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        assert_eq!(config.sources[0].name, "browser");
+        assert_eq!(
+            config.locale_fallback,
+            Some(LocaleFallback {
+                default_locale: Some(en_us),
+                likely_subtags: false,
+            })
+        );
+        assert!(config.solver_options.greedy);
+        assert_eq!(config.solver_options.lookahead, Lookahead::Candidates(2));
+        assert_eq!(
+            RegistryConfig::from_json(&config.to_json().unwrap()),
+            Ok(config)
+        );
+
+        assert_eq!(
+            RegistryConfig::from_json("{}"),
+            Ok(RegistryConfig::default())
+        );
+        assert!(matches!(
+            RegistryConfig::from_json(r#"{"sources": [{"name": "browser"}]}"#),
+            Err(L10nRegistrySetupError::InvalidConfig { .. })
+        ));
+    }
+}
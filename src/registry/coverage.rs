@@ -0,0 +1,219 @@
+use super::{L10nRegistry, ResourceId};
+use crate::errors::L10nRegistrySetupError;
+use crate::family::Family;
+
+use fluent_bundle::FluentResource;
+use fluent_syntax::ast;
+use rustc_hash::FxHashSet;
+use unic_langid::LanguageIdentifier;
+
+/// Describes which of the requested resources are available for each locale
+/// of an [`L10nRegistry`], compared to a reference locale.
+///
+/// See [`L10nRegistry::coverage_report`].
+///
+/// [`L10nRegistry`]: struct.L10nRegistry.html
+/// [`L10nRegistry::coverage_report`]: struct.L10nRegistry.html#method.coverage_report
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    pub reference: LanguageIdentifier,
+    /// The coverage of each available locale, including the reference
+    /// locale.
+    pub locales: Vec<LocaleCoverage>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocaleCoverage {
+    pub locale: LanguageIdentifier,
+    /// The coverage of each requested resource, in the requested order.
+    pub resources: Vec<ResourceCoverage>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceCoverage {
+    pub res_id: String,
+    /// The names of the sources providing the resource, in the order they
+    /// are tried by the bundle generators.
+    pub sources: Vec<String>,
+    /// The number of distinct messages in the resource, across all the
+    /// `sources`.
+    pub message_count: usize,
+    /// The ids of the messages of the resource in the reference locale which
+    /// are missing in this locale.
+    pub missing_messages: Vec<String>,
+}
+
+impl ResourceCoverage {
+    pub fn is_available(&self) -> bool {
+        !self.sources.is_empty()
+    }
+}
+
+impl LocaleCoverage {
+    /// Whether all the resources are available, with all the messages of the
+    /// reference locale.
+    pub fn is_complete(&self) -> bool {
+        self.resources
+            .iter()
+            .all(|res| res.is_available() && res.missing_messages.is_empty())
+    }
+
+    pub fn missing_message_count(&self) -> usize {
+        self.resources
+            .iter()
+            .map(|res| res.missing_messages.len())
+            .sum()
+    }
+}
+
+impl CoverageReport {
+    pub fn get_locale(&self, locale: &LanguageIdentifier) -> Option<&LocaleCoverage> {
+        self.locales
+            .iter()
+            .find(|coverage| &coverage.locale == locale)
+    }
+
+    /// Serialize the report as tab separated values, with one line per
+    /// locale and resource:
+    ///
+    /// ```text
+    /// locale  res_id  sources  message_count  missing_messages
+    /// ```
+    ///
+    /// The sources and the missing messages are separated with commas.
+    /// Backslashes, tabs and line breaks in the values are escaped as `\\`,
+    /// `\t`, `\n` and `\r`.
+    pub fn to_tsv(&self) -> String {
+        let mut result = String::from("locale\tres_id\tsources\tmessage_count\tmissing_messages\n");
+        for coverage in &self.locales {
+            for res in &coverage.resources {
+                result.push_str(&format!(
+                    "{}\t{}\t{}\t{}\t{}\n",
+                    coverage.locale,
+                    escape_tsv(&res.res_id),
+                    escape_tsv(&res.sources.join(",")),
+                    res.message_count,
+                    escape_tsv(&res.missing_messages.join(","))
+                ));
+            }
+        }
+        result
+    }
+}
+
+fn escape_tsv(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\t' => result.push_str("\\t"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// The ids of the messages of a resource across the sources providing it,
+/// in the order they are first found.
+#[derive(Default)]
+struct MessageIds {
+    ids: Vec<String>,
+    seen: FxHashSet<String>,
+}
+
+impl MessageIds {
+    fn extend(&mut self, resource: &FluentResource) {
+        for entry in resource.entries() {
+            if let ast::Entry::Message(message) = entry {
+                if self.seen.insert(message.id.name.to_string()) {
+                    self.ids.push(message.id.name.to_string());
+                }
+            }
+        }
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.seen.contains(id)
+    }
+}
+
+impl<P, B, F: Family> L10nRegistry<P, B, F> {
+    /// Report the coverage of `res_ids` for every locale returned by
+    /// [`get_available_locales`](#method.get_available_locales), comparing
+    /// the messages of each locale with the ones of the `reference` locale.
+    ///
+    /// The messages of a resource are collected from all the sources
+    /// providing it. The sources whose index doesn't list the resource are
+    /// skipped, and the resources are otherwise fetched synchronously, with
+    /// blocking I/O, from all the sources declaring the locale. They stay
+    /// cached in the sources.
+    ///
+    /// No messages are reported as missing if the `reference` locale isn't
+    /// available.
+    pub fn coverage_report(
+        &self,
+        reference: &LanguageIdentifier,
+        res_ids: &[ResourceId],
+    ) -> Result<CoverageReport, L10nRegistrySetupError> {
+        let locales = self.get_available_locales()?;
+
+        let lock = self.lock();
+        let mut report: Vec<(LocaleCoverage, Vec<MessageIds>)> = locales
+            .into_iter()
+            .map(|locale| {
+                let mut resources = vec![];
+                let mut messages = vec![];
+                for res_id in res_ids {
+                    let mut sources = vec![];
+                    let mut ids = MessageIds::default();
+                    for source_idx in 0..lock.len() {
+                        let source = lock.source_idx(source_idx);
+                        if source.has_file(&locale, &res_id.value) == Some(false) {
+                            continue;
+                        }
+                        if let Some(res) = source.fetch_file_sync(&locale, &res_id.value, false) {
+                            sources.push(source.name.clone());
+                            ids.extend(&res);
+                        }
+                    }
+                    resources.push(ResourceCoverage {
+                        res_id: res_id.value.clone(),
+                        sources,
+                        message_count: ids.ids.len(),
+                        missing_messages: vec![],
+                    });
+                    messages.push(ids);
+                }
+                (LocaleCoverage { locale, resources }, messages)
+            })
+            .collect();
+        drop(lock);
+
+        let reference_messages: Vec<Vec<String>> = report
+            .iter()
+            .find(|(coverage, _)| &coverage.locale == reference)
+            .map(|(_, messages)| messages.iter().map(|ids| ids.ids.clone()).collect())
+            .unwrap_or_default();
+        for (coverage, messages) in &mut report {
+            for ((res, ids), reference_ids) in coverage
+                .resources
+                .iter_mut()
+                .zip(messages.iter())
+                .zip(reference_messages.iter())
+            {
+                res.missing_messages = reference_ids
+                    .iter()
+                    .filter(|id| !ids.contains(id))
+                    .cloned()
+                    .collect();
+            }
+        }
+
+        Ok(CoverageReport {
+            reference: reference.clone(),
+            locales: report.into_iter().map(|(coverage, _)| coverage).collect(),
+        })
+    }
+}
@@ -0,0 +1,120 @@
+use unic_langid::{subtags::Script, LanguageIdentifier};
+
+/// Expands the locales requested from an [`L10nRegistry`] with their less
+/// specific fallbacks, so that sources declaring, for example, `de` can
+/// satisfy a request for `de-AT`.
+///
+/// Each requested locale is followed by its fallbacks, created by stripping
+/// the variants, the region and the script. Duplicates are skipped, and the
+/// `default_locale` is appended last.
+///
+/// With `likely_subtags`, a fallback is skipped if it implies a different
+/// script than the requested locale, and the script is kept instead, so that
+/// `zh-TW` falls back to `zh-Hant` rather than `zh`.
+///
+/// See [`L10nRegistry::set_locale_fallback`].
+///
+/// [`L10nRegistry`]: struct.L10nRegistry.html
+/// [`L10nRegistry::set_locale_fallback`]: struct.L10nRegistry.html#method.set_locale_fallback
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LocaleFallback {
+    pub default_locale: Option<LanguageIdentifier>,
+    pub likely_subtags: bool,
+}
+
+impl LocaleFallback {
+    pub fn expand<I>(&self, locales: I) -> Vec<LanguageIdentifier>
+    where
+        I: IntoIterator<Item = LanguageIdentifier>,
+    {
+        let mut result = vec![];
+        for locale in locales {
+            for fallback in self.fallback_chain(locale) {
+                push_unique(&mut result, fallback);
+            }
+        }
+        if let Some(default_locale) = &self.default_locale {
+            push_unique(&mut result, default_locale.clone());
+        }
+        result
+    }
+
+    fn fallback_chain(&self, locale: LanguageIdentifier) -> Vec<LanguageIdentifier> {
+        let script = if self.likely_subtags {
+            likely_script(&locale)
+        } else {
+            None
+        };
+        let implies_script =
+            |locale: &LanguageIdentifier| script.is_none() || likely_script(locale) == script;
+
+        let mut current = locale.clone();
+        let mut chain = vec![locale];
+        if current.variants().len() > 0 {
+            current.clear_variants();
+            chain.push(current.clone());
+        }
+        if current.region.take().is_some() {
+            if current.script.is_none() && !implies_script(&current) {
+                current.script = script;
+            }
+            chain.push(current.clone());
+        }
+        if current.script.take().is_some() && implies_script(&current) {
+            chain.push(current);
+        }
+        chain
+    }
+}
+
+fn likely_script(locale: &LanguageIdentifier) -> Option<Script> {
+    let mut locale = locale.clone();
+    locale.maximize();
+    locale.script
+}
+
+fn push_unique(locales: &mut Vec<LanguageIdentifier>, locale: LanguageIdentifier) {
+    if !locales.contains(&locale) {
+        locales.push(locale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn langids(locales: &[&str]) -> Vec<LanguageIdentifier> {
+        locales.iter().map(|l| l.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn locale_fallback_expand() {
+        let fallback = LocaleFallback {
+            default_locale: Some("en-US".parse().unwrap()),
+            likely_subtags: false,
+        };
+        assert_eq!(
+            fallback.expand(langids(&["de-AT", "sr-Latn-RS", "de"])),
+            langids(&["de-AT", "de", "sr-Latn-RS", "sr-Latn", "sr", "en-US"])
+        );
+        assert_eq!(
+            fallback.expand(langids(&["en-US-macos"])),
+            langids(&["en-US-macos", "en-US", "en"])
+        );
+        assert_eq!(fallback.expand(vec![]), langids(&["en-US"]));
+    }
+
+    #[test]
+    fn locale_fallback_likely_subtags() {
+        let fallback = LocaleFallback {
+            default_locale: None,
+            likely_subtags: true,
+        };
+        assert_eq!(
+            fallback.expand(langids(&["de-AT", "zh-TW", "sr-Latn-RS"])),
+            langids(&["de-AT", "de", "zh-TW", "zh-Hant", "sr-Latn-RS", "sr-Latn"])
+        );
+    }
+}
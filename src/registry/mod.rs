@@ -1,40 +1,102 @@
 mod asynchronous;
+mod availability;
+mod config;
+mod coverage;
+mod fallback;
+mod resource_id;
 mod synchronous;
 
-use std::{
-    cell::{Ref, RefCell},
-    collections::HashSet,
-    rc::Rc,
-};
+use std::{collections::HashSet, rc::Rc};
 
-use crate::errors::L10nRegistrySetupError;
-use crate::source::FileSource;
+use crate::errors::{L10nRegistryError, L10nRegistrySetupError};
+use crate::family::{
+    private::{Counter, Listener, Lock, Pointer},
+    Bundle, Family, IntoListener, Local,
+};
+use crate::solver::SolverOptions;
+use crate::source::{FileSource, StartupCache};
+use availability::AvailabilityMatrix;
 
 use crate::env::ErrorReporter;
-use crate::fluent::FluentBundle;
 use fluent_bundle::FluentResource;
 use fluent_fallback::generator::BundleGenerator;
+use fluent_langneg::negotiate_languages;
+use intl_memoizer::IntlLangMemoizer;
 use unic_langid::LanguageIdentifier;
 
 pub use asynchronous::GenerateBundles;
+pub use config::RegistryConfig;
+pub use coverage::{CoverageReport, LocaleCoverage, ResourceCoverage};
+pub use fallback::LocaleFallback;
+pub use fluent_langneg::NegotiationStrategy;
+pub use resource_id::{ResourceId, ResourceType};
 pub use synchronous::GenerateBundlesSync;
 
 pub type FluentResourceSet = Vec<Rc<FluentResource>>;
 
-#[derive(Default)]
-struct Shared<P, B> {
-    sources: RefCell<Vec<FileSource>>,
+/// A resource which has changed in one of the sources of the registry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceChange {
+    pub source: String,
+    pub locale: LanguageIdentifier,
+    pub res_id: String,
+}
+
+/// Describes where the resources of a generated bundle come from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleMetadata {
+    pub locale: LanguageIdentifier,
+    /// The resources added to the bundle, in the requested order. Optional
+    /// resources missing in all sources are not listed.
+    pub resources: Vec<ResourceOrigin>,
+}
+
+/// The source a resource of a bundle has been loaded from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceOrigin {
+    pub res_id: String,
+    pub source: String,
+    pub path: String,
+}
+
+/// A bundle generator yielding, along with each bundle, the
+/// [`BundleMetadata`](struct.BundleMetadata.html) of the bundle.
+///
+/// See [`GenerateBundlesSync::with_metadata`] and
+/// [`GenerateBundles::with_metadata`].
+///
+/// [`GenerateBundlesSync::with_metadata`]: struct.GenerateBundlesSync.html#method.with_metadata
+/// [`GenerateBundles::with_metadata`]: struct.GenerateBundles.html#method.with_metadata
+pub struct WithMetadata<G>(G);
+
+struct Shared<P, B, F: Family> {
+    sources: F::Lock<Vec<FileSource<F>>>,
     provider: P,
     bundle_adapter: Option<B>,
+    locale_fallback: Option<LocaleFallback>,
+    solver_options: SolverOptions,
+    /// The resources a locale has to provide to be negotiated.
+    required_resources: Vec<ResourceId>,
+    /// The availability of the resources in the sources, shared by the
+    /// bundle generators.
+    availability: F::Ptr<F::Lock<AvailabilityMatrix>>,
+    listeners: F::Lock<Vec<F::Listener>>,
+    /// Set to a new generation every time the sources are registered,
+    /// updated or removed.
+    generation: F::Counter,
 }
 
-pub struct L10nRegistryLocked<'a, B> {
-    lock: Ref<'a, Vec<FileSource>>,
+type SourcesGuard<'a, F> =
+    <<F as Family>::Lock<Vec<FileSource<F>>> as Lock<Vec<FileSource<F>>>>::ReadGuard<'a>;
+
+pub struct L10nRegistryLocked<'a, B, F: Family = Local> {
+    lock: SourcesGuard<'a, F>,
     bundle_adapter: Option<&'a B>,
+    generation: usize,
 }
 
-impl<'a, B> L10nRegistryLocked<'a, B> {
-    pub fn iter(&self) -> impl Iterator<Item = &FileSource> {
+impl<'a, B, F: Family> L10nRegistryLocked<'a, B, F> {
+    pub fn iter(&self) -> impl Iterator<Item = &FileSource<F>> {
         self.lock.iter()
     }
 
@@ -46,70 +108,198 @@ impl<'a, B> L10nRegistryLocked<'a, B> {
         self.len() == 0
     }
 
-    pub fn source_idx(&self, index: usize) -> &FileSource {
+    pub fn source_idx(&self, index: usize) -> &FileSource<F> {
         let source_idx = self.len() - 1 - index;
         self.lock.get(source_idx).expect("Index out-of-range")
     }
 
-    pub fn get_source(&self, name: &str) -> Option<&FileSource> {
+    pub fn get_source(&self, name: &str) -> Option<&FileSource<F>> {
         self.lock.iter().find(|&source| source.name == name)
     }
 
+    /// Build the error reported when `res_id` is missing for `locale` in
+    /// all the sources.
+    pub(crate) fn missing_resource(
+        &self,
+        locale: &LanguageIdentifier,
+        res_id: &str,
+    ) -> L10nRegistryError {
+        L10nRegistryError::MissingResource {
+            locale: locale.clone(),
+            res_id: res_id.to_string(),
+            sources: (0..self.len())
+                .map(|idx| self.source_idx(idx).lookup(locale, res_id))
+                .collect(),
+        }
+    }
+
+    /// Returns a number which changes every time the cache of any of the
+    /// sources is invalidated, or the sources are changed.
+    ///
+    /// Since each change takes a new generation, greater than all the
+    /// previous ones, the greatest generation changes with any of them.
+    pub(crate) fn cache_generation(&self) -> usize {
+        self.iter()
+            .map(|source| source.cache_generation())
+            .fold(self.generation, usize::max)
+    }
+
+    /// Describe the bundle built from `source_order` by `bundle_from_order`.
+    pub(crate) fn bundle_metadata(
+        &self,
+        locale: &LanguageIdentifier,
+        source_order: &[usize],
+        res_ids: &[ResourceId],
+    ) -> BundleMetadata {
+        let resources = source_order
+            .iter()
+            .zip(res_ids.iter())
+            .filter(|(&source_idx, _)| source_idx != self.len())
+            .map(|(&source_idx, res_id)| {
+                let source = self.source_idx(source_idx);
+                ResourceOrigin {
+                    res_id: res_id.value.clone(),
+                    source: source.name.clone(),
+                    path: source.get_path(locale, &res_id.value),
+                }
+            })
+            .collect();
+        BundleMetadata {
+            locale: locale.clone(),
+            resources,
+        }
+    }
+
     pub fn generate_sources_for_file<'l>(
         &'l self,
         langid: &'l LanguageIdentifier,
         res_id: &'l str,
-    ) -> impl Iterator<Item = &FileSource> {
+    ) -> impl Iterator<Item = &'l FileSource<F>> {
         self.iter()
             .filter(move |source| source.has_file(langid, res_id) != Some(false))
     }
 }
 
-pub trait BundleAdapter {
-    fn adapt_bundle(&self, bundle: &mut FluentBundle);
+/// Insert `source` after all the sources with the same or a lower priority,
+/// so that the solver tries it before them.
+fn insert_source<F: Family>(sources: &mut Vec<FileSource<F>>, source: FileSource<F>) {
+    let idx = sources
+        .iter()
+        .position(|s| s.options.priority > source.options.priority)
+        .unwrap_or(sources.len());
+    sources.insert(idx, source);
+}
+
+pub trait BundleAdapter<F: Family = Local> {
+    fn adapt_bundle(&self, bundle: &mut Bundle<F>);
+}
+
+pub struct L10nRegistry<P, B, F: Family = Local> {
+    shared: F::Ptr<Shared<P, B, F>>,
 }
 
-#[derive(Clone)]
-pub struct L10nRegistry<P, B> {
-    shared: Rc<Shared<P, B>>,
+impl<P, B, F: Family> Clone for L10nRegistry<P, B, F> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
 }
 
-impl<P, B> L10nRegistry<P, B> {
+impl<P, B, F: Family> L10nRegistry<P, B, F> {
     pub fn with_provider(provider: P) -> Self {
         Self {
-            shared: Rc::new(Shared {
-                sources: Default::default(),
+            shared: Pointer::new(Shared {
+                sources: Lock::new(vec![]),
                 provider,
                 bundle_adapter: None,
+                locale_fallback: None,
+                solver_options: SolverOptions::default(),
+                required_resources: vec![],
+                availability: Pointer::new(Lock::new(AvailabilityMatrix::default())),
+                listeners: Lock::new(vec![]),
+                generation: Default::default(),
             }),
         }
     }
 
     pub fn set_adapt_bundle(&mut self, bundle_adapter: B) -> Result<(), L10nRegistrySetupError>
     where
-        B: BundleAdapter,
+        B: BundleAdapter<F>,
     {
-        let shared = Rc::get_mut(&mut self.shared).ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        let shared =
+            Pointer::get_mut(&mut self.shared).ok_or(L10nRegistrySetupError::RegistryLocked)?;
         shared.bundle_adapter = Some(bundle_adapter);
         Ok(())
     }
 
-    pub fn lock(&self) -> L10nRegistryLocked<'_, B> {
+    /// Expand the locales passed to the bundle generators with their
+    /// fallbacks. By default, the locales are used verbatim.
+    pub fn set_locale_fallback(
+        &mut self,
+        locale_fallback: LocaleFallback,
+    ) -> Result<(), L10nRegistrySetupError> {
+        let shared =
+            Pointer::get_mut(&mut self.shared).ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        shared.locale_fallback = Some(locale_fallback);
+        Ok(())
+    }
+
+    /// Limit the solutions the bundle generators yield for each locale. By
+    /// default, all the combinations of the sources are yielded.
+    pub fn set_solver_options(
+        &mut self,
+        solver_options: SolverOptions,
+    ) -> Result<(), L10nRegistrySetupError> {
+        let shared =
+            Pointer::get_mut(&mut self.shared).ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        shared.solver_options = solver_options;
+        Ok(())
+    }
+
+    /// Set the resources a locale has to provide to be returned by
+    /// [`negotiate_locales`](#method.negotiate_locales). The optional
+    /// resources are ignored. By default, all the available locales are
+    /// negotiated.
+    pub fn set_required_resources(
+        &mut self,
+        res_ids: Vec<ResourceId>,
+    ) -> Result<(), L10nRegistrySetupError> {
+        let shared =
+            Pointer::get_mut(&mut self.shared).ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        shared.required_resources = res_ids;
+        Ok(())
+    }
+
+    /// Return the locales the bundle generators try for the requested
+    /// `locales`.
+    pub fn expand_locales<I>(&self, locales: I) -> Vec<LanguageIdentifier>
+    where
+        I: IntoIterator<Item = LanguageIdentifier>,
+    {
+        match &self.shared.locale_fallback {
+            Some(locale_fallback) => locale_fallback.expand(locales),
+            None => locales.into_iter().collect(),
+        }
+    }
+
+    pub fn lock(&self) -> L10nRegistryLocked<'_, B, F> {
         L10nRegistryLocked {
-            lock: self.shared.sources.borrow(),
+            lock: self.shared.sources.read(),
             bundle_adapter: self.shared.bundle_adapter.as_ref(),
+            generation: self.shared.generation.get(),
         }
     }
 
     pub fn register_sources(
         &self,
-        new_sources: Vec<FileSource>,
+        new_sources: Vec<FileSource<F>>,
     ) -> Result<(), L10nRegistrySetupError> {
         let mut sources = self
             .shared
             .sources
-            .try_borrow_mut()
-            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
+            .try_write()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
 
         for new_source in new_sources {
             if sources.iter().any(|source| source == &new_source) {
@@ -117,30 +307,37 @@ impl<P, B> L10nRegistry<P, B> {
                     name: new_source.name,
                 });
             }
-            sources.push(new_source);
+            insert_source(&mut sources, new_source);
         }
+        self.clear_availability();
         Ok(())
     }
 
     pub fn update_sources(
         &self,
-        upd_sources: Vec<FileSource>,
+        upd_sources: Vec<FileSource<F>>,
     ) -> Result<(), L10nRegistrySetupError> {
         let mut sources = self
             .shared
             .sources
-            .try_borrow_mut()
-            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
+            .try_write()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
 
         for upd_source in upd_sources {
             if let Some(idx) = sources.iter().position(|source| *source == upd_source) {
-                *sources.get_mut(idx).unwrap() = upd_source;
+                if sources[idx].options.priority == upd_source.options.priority {
+                    sources[idx] = upd_source;
+                } else {
+                    sources.remove(idx);
+                    insert_source(&mut sources, upd_source);
+                }
             } else {
                 return Err(L10nRegistrySetupError::MissingSource {
                     name: upd_source.name,
                 });
             }
         }
+        self.clear_availability();
         Ok(())
     }
 
@@ -151,11 +348,12 @@ impl<P, B> L10nRegistry<P, B> {
         let mut sources = self
             .shared
             .sources
-            .try_borrow_mut()
-            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
+            .try_write()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
         let del_sources: Vec<String> = del_sources.into_iter().map(|s| s.to_string()).collect();
 
         sources.retain(|source| !del_sources.contains(&source.name));
+        self.clear_availability();
         Ok(())
     }
 
@@ -163,18 +361,55 @@ impl<P, B> L10nRegistry<P, B> {
         let mut sources = self
             .shared
             .sources
-            .try_borrow_mut()
-            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
+            .try_write()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
         sources.clear();
+        self.clear_availability();
         Ok(())
     }
 
+    /// Take a snapshot of the caches of all the sources, to be persisted and
+    /// restored on the next start with
+    /// [`restore_startup_cache`](#method.restore_startup_cache).
+    pub fn startup_cache(&self) -> Result<StartupCache, L10nRegistrySetupError> {
+        let sources = self
+            .shared
+            .sources
+            .try_read()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        Ok(StartupCache {
+            sources: sources.iter().map(FileSource::snapshot).collect(),
+        })
+    }
+
+    /// Pre-populate the caches of the registered sources with `cache`,
+    /// returning the number of restored resources.
+    ///
+    /// See [`FileSource::restore_snapshot`](../source/struct.FileSource.html#method.restore_snapshot).
+    pub fn restore_startup_cache(
+        &self,
+        cache: &StartupCache,
+    ) -> Result<usize, L10nRegistrySetupError> {
+        let sources = self
+            .shared
+            .sources
+            .try_read()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        Ok(sources
+            .iter()
+            .filter_map(|source| {
+                let snapshot = cache.get_source(&source.name)?;
+                Some(source.restore_snapshot(snapshot))
+            })
+            .sum())
+    }
+
     pub fn get_source_names(&self) -> Result<Vec<String>, L10nRegistrySetupError> {
         let sources = self
             .shared
             .sources
-            .try_borrow_mut()
-            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
+            .try_write()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
         Ok(sources.iter().map(|s| s.name.clone()).collect())
     }
 
@@ -182,44 +417,189 @@ impl<P, B> L10nRegistry<P, B> {
         let sources = self
             .shared
             .sources
-            .try_borrow_mut()
-            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
+            .try_write()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
         Ok(sources.iter().any(|source| source.name == name))
     }
 
-    pub fn get_source(&self, name: &str) -> Result<Option<FileSource>, L10nRegistrySetupError> {
+    pub fn get_source(&self, name: &str) -> Result<Option<FileSource<F>>, L10nRegistrySetupError> {
         let sources = self
             .shared
             .sources
-            .try_borrow_mut()
-            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
+            .try_write()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
         Ok(sources.iter().find(|source| source.name == name).cloned())
     }
 
+    /// Invalidate the cached resource `res_id` for `locale` in all sources.
+    pub fn invalidate_resource(
+        &self,
+        locale: &LanguageIdentifier,
+        res_id: &str,
+    ) -> Result<(), L10nRegistrySetupError> {
+        let sources = self
+            .shared
+            .sources
+            .try_read()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        for source in sources.iter() {
+            source.invalidate(locale, res_id);
+        }
+        Ok(())
+    }
+
+    /// Invalidate all cached resources for `locale` in all sources.
+    pub fn invalidate_locale(
+        &self,
+        locale: &LanguageIdentifier,
+    ) -> Result<(), L10nRegistrySetupError> {
+        let sources = self
+            .shared
+            .sources
+            .try_read()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        for source in sources.iter() {
+            source.invalidate_locale(locale);
+        }
+        Ok(())
+    }
+
+    /// Invalidate all cached resources in all sources.
+    pub fn clear_caches(&self) -> Result<(), L10nRegistrySetupError> {
+        let sources = self
+            .shared
+            .sources
+            .try_read()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        for source in sources.iter() {
+            source.clear_cache();
+        }
+        Ok(())
+    }
+
+    /// Register a callback which is invoked with the list of changed
+    /// resources every time changes are reported through
+    /// [`notify_changes`](#method.notify_changes), for example by the
+    /// `RegistryWatcher` of the `watcher` feature.
+    ///
+    /// A listener can use it to call `Localization::on_change` and
+    /// retranslate.
+    pub fn add_change_listener<L>(&self, listener: L)
+    where
+        L: Fn(&[ResourceChange]) + IntoListener<F>,
+    {
+        self.shared.listeners.write().push(listener.into_listener());
+    }
+
+    /// Invoke all the listeners registered with
+    /// [`add_change_listener`](#method.add_change_listener).
+    ///
+    /// The listeners may register other listeners, which are first invoked
+    /// on the next notification.
+    pub fn notify_changes(&self, changes: &[ResourceChange]) {
+        let listeners = self.shared.listeners.read().clone();
+        for listener in listeners {
+            listener.call(changes);
+        }
+    }
+
+    /// Return the locales of all the sources, in the order of the sources
+    /// they are first declared by.
     pub fn get_available_locales(&self) -> Result<Vec<LanguageIdentifier>, L10nRegistrySetupError> {
         let sources = self
             .shared
             .sources
-            .try_borrow_mut()
-            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
-        let mut result = HashSet::new();
+            .try_write()
+            .ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        let mut seen = HashSet::new();
+        let mut result = vec![];
         for source in sources.iter() {
             for locale in source.locales() {
-                result.insert(locale);
+                if seen.insert(locale) {
+                    result.push(locale.clone());
+                }
             }
         }
-        Ok(result.into_iter().map(|l| l.to_owned()).collect())
+        Ok(result)
+    }
+
+    /// Negotiate the `requested` locales against the complete locales among
+    /// the ones returned by
+    /// [`get_available_locales`](#method.get_available_locales), and return
+    /// the ordered list of locales to generate bundles for.
+    ///
+    /// The `default` locale is appended as described by the `strategy`.
+    ///
+    /// A locale is complete if each of the resources set with
+    /// [`set_required_resources`](#method.set_required_resources) is provided
+    /// by one of the sources. Only the available locales matching one of the
+    /// `requested` locales are checked. Their completeness is resolved from
+    /// the indexes and the caches of the sources, and from the availability
+    /// found by the bundle generators, so no resource is fetched and the call
+    /// doesn't wait for any I/O. It takes the locks of the sources and of
+    /// the availability, and looks up each required resource in each source
+    /// for each of those locales. The resources whose availability isn't
+    /// known yet are assumed to be provided.
+    pub fn negotiate_locales(
+        &self,
+        requested: &[LanguageIdentifier],
+        default: Option<&LanguageIdentifier>,
+        strategy: NegotiationStrategy,
+    ) -> Result<Vec<LanguageIdentifier>, L10nRegistrySetupError> {
+        let available = self.get_available_locales()?;
+        let mut candidates: Vec<LanguageIdentifier> =
+            negotiate_languages(requested, &available, None, NegotiationStrategy::Filtering)
+                .into_iter()
+                .cloned()
+                .collect();
+
+        let required: Vec<&ResourceId> = self
+            .shared
+            .required_resources
+            .iter()
+            .filter(|res_id| !res_id.is_optional())
+            .collect();
+        if !required.is_empty() {
+            let lock = self.lock();
+            let mut matrix = self.shared.availability.write();
+            matrix.sync_generation(lock.cache_generation());
+            candidates.retain(|locale| {
+                required.iter().all(|res_id| {
+                    lock.iter().any(|source| {
+                        source
+                            .has_file(locale, &res_id.value)
+                            .or_else(|| {
+                                matrix.get(
+                                    locale,
+                                    &res_id.value,
+                                    &source.name,
+                                    source.missing_ttl(),
+                                )
+                            })
+                            .unwrap_or(true)
+                    })
+                })
+            });
+        }
+
+        Ok(
+            negotiate_languages(requested, &candidates, default, strategy)
+                .into_iter()
+                .cloned()
+                .collect(),
+        )
     }
 }
 
-impl<P, B> BundleGenerator for L10nRegistry<P, B>
+impl<P, B, F> BundleGenerator for L10nRegistry<P, B, F>
 where
     P: ErrorReporter + Clone,
-    B: BundleAdapter + Clone,
+    B: BundleAdapter<F> + Clone,
+    F: Family<Memoizer = IntlLangMemoizer>,
 {
-    type Resource = Rc<FluentResource>;
-    type Iter = GenerateBundlesSync<P, B>;
-    type Stream = GenerateBundles<P, B>;
+    type Resource = F::Ptr<FluentResource>;
+    type Iter = GenerateBundlesSync<P, B, F>;
+    type Stream = GenerateBundles<P, B, F>;
     type LocalesIter = std::vec::IntoIter<LanguageIdentifier>;
 
     fn bundles_iter(&self, locales: Self::LocalesIter, resource_ids: Vec<String>) -> Self::Iter {
@@ -0,0 +1,65 @@
+use std::fmt;
+
+/// Whether a resource has to be available for a bundle to be generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ResourceType {
+    /// A locale missing the resource in all sources is skipped.
+    #[default]
+    Required,
+    /// A bundle is generated without the resource if it's missing in all
+    /// sources.
+    Optional,
+}
+
+/// The identifier of a resource requested from an [`L10nRegistry`], which
+/// is a path relative to the directory of a locale in a source.
+///
+/// Resource identifiers converted from strings are required.
+///
+/// [`L10nRegistry`]: struct.L10nRegistry.html
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResourceId {
+    pub value: String,
+    pub resource_type: ResourceType,
+}
+
+impl ResourceId {
+    pub fn new<S: Into<String>>(value: S, resource_type: ResourceType) -> Self {
+        Self {
+            value: value.into(),
+            resource_type,
+        }
+    }
+
+    pub fn optional<S: Into<String>>(value: S) -> Self {
+        Self::new(value, ResourceType::Optional)
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.resource_type == ResourceType::Optional
+    }
+}
+
+impl fmt::Display for ResourceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl From<String> for ResourceId {
+    fn from(value: String) -> Self {
+        Self::new(value, ResourceType::Required)
+    }
+}
+
+impl From<&str> for ResourceId {
+    fn from(value: &str) -> Self {
+        Self::new(value, ResourceType::Required)
+    }
+}
+
+impl AsRef<str> for ResourceId {
+    fn as_ref(&self) -> &str {
+        &self.value
+    }
+}
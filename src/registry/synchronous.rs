@@ -1,46 +1,60 @@
-use super::{BundleAdapter, L10nRegistry, L10nRegistryLocked};
+use super::{
+    BundleAdapter, BundleMetadata, L10nRegistry, L10nRegistryLocked, ResourceId, WithMetadata,
+};
 use crate::env::ErrorReporter;
 use crate::errors::L10nRegistryError;
-use crate::fluent::{FluentBundle, FluentError};
-use crate::solver::{SerialProblemSolver, SyncTester};
+use crate::family::{private::BundleMemoizer, Bundle, BundleResult, Family, Local, Resource};
+use crate::solver::{SerialProblemSolver, SolverOptions, SyncTester};
+use crate::source::add_resource_errors;
 use fluent_fallback::generator::BundleIterator;
 
 use unic_langid::LanguageIdentifier;
 
-impl<'a, B> L10nRegistryLocked<'a, B> {
+impl<'a, B, F: Family> L10nRegistryLocked<'a, B, F> {
     pub(crate) fn bundle_from_order<P>(
         &self,
         locale: LanguageIdentifier,
         source_order: &[usize],
-        res_ids: &[String],
+        res_ids: &[ResourceId],
         error_reporter: &P,
-    ) -> Option<Result<FluentBundle, (FluentBundle, Vec<FluentError>)>>
+    ) -> Option<BundleResult<F>>
     where
         P: ErrorReporter,
-        B: BundleAdapter,
+        B: BundleAdapter<F>,
     {
-        let mut bundle = FluentBundle::new(vec![locale.clone()]);
+        let mut bundle: Bundle<F> = F::Memoizer::new_bundle(vec![locale.clone()]);
 
         if let Some(bundle_adapter) = self.bundle_adapter {
             bundle_adapter.adapt_bundle(&mut bundle);
         }
 
         let mut errors = vec![];
+        let mut added: Vec<(String, Resource<F>)> = vec![];
 
-        for (&source_idx, path) in source_order.iter().zip(res_ids.iter()) {
+        for (&source_idx, res_id) in source_order.iter().zip(res_ids.iter()) {
+            if source_idx == self.len() {
+                errors.push(L10nRegistryError::MissingOptionalResource {
+                    locale: locale.clone(),
+                    res_id: res_id.value.clone(),
+                });
+                continue;
+            }
+            let path = &res_id.value;
             let source = self.source_idx(source_idx);
             if let Some(res) = source.fetch_file_sync(&locale, path, false) {
+                let full_path = source.get_path(&locale, path);
                 if source.options.allow_override {
-                    bundle.add_resource_overriding(res);
-                } else if let Err(err) = bundle.add_resource(res) {
-                    errors.extend(err.into_iter().map(|error| {
-                        L10nRegistryError::FluentError {
-                            path: path.clone(),
-                            loc: None,
-                            error,
-                        }
-                    }));
+                    bundle.add_resource_overriding(res.clone());
+                } else if let Err(err) = bundle.add_resource(res.clone()) {
+                    errors.extend(add_resource_errors(
+                        &source.name,
+                        &full_path,
+                        &res,
+                        &added,
+                        err,
+                    ));
                 }
+                added.push((full_path, res));
             } else {
                 return None;
             }
@@ -53,31 +67,34 @@ impl<'a, B> L10nRegistryLocked<'a, B> {
     }
 }
 
-impl<P, B> L10nRegistry<P, B>
+impl<P, B, F: Family> L10nRegistry<P, B, F>
 where
     P: Clone,
     B: Clone,
 {
-    pub fn generate_bundles_for_lang_sync(
+    pub fn generate_bundles_for_lang_sync<R: Into<ResourceId>>(
         &self,
         langid: LanguageIdentifier,
-        resource_ids: Vec<String>,
-    ) -> GenerateBundlesSync<P, B> {
-        let lang_ids = vec![langid];
+        resource_ids: Vec<R>,
+    ) -> GenerateBundlesSync<P, B, F> {
+        let lang_ids = self.expand_locales(vec![langid]);
 
         GenerateBundlesSync::new(self.clone(), lang_ids.into_iter(), resource_ids)
     }
 
-    pub fn generate_bundles_sync(
+    pub fn generate_bundles_sync<R: Into<ResourceId>>(
         &self,
         locales: std::vec::IntoIter<LanguageIdentifier>,
-        resource_ids: Vec<String>,
-    ) -> GenerateBundlesSync<P, B> {
-        GenerateBundlesSync::new(self.clone(), locales, resource_ids)
+        resource_ids: Vec<R>,
+    ) -> GenerateBundlesSync<P, B, F> {
+        let locales = self.expand_locales(locales);
+        GenerateBundlesSync::new(self.clone(), locales.into_iter(), resource_ids)
     }
 }
 
+#[derive(Default)]
 enum State {
+    #[default]
     Empty,
     Locale(LanguageIdentifier),
     Solver {
@@ -86,12 +103,6 @@ enum State {
     },
 }
 
-impl Default for State {
-    fn default() -> Self {
-        Self::Empty
-    }
-}
-
 impl State {
     fn get_locale(&self) -> &LanguageIdentifier {
         match self {
@@ -116,97 +127,160 @@ impl State {
     }
 }
 
-pub struct GenerateBundlesSync<P, B> {
-    reg: L10nRegistry<P, B>,
+pub struct GenerateBundlesSync<P, B, F: Family = Local> {
+    reg: L10nRegistry<P, B, F>,
     locales: std::vec::IntoIter<LanguageIdentifier>,
-    res_ids: Vec<String>,
+    res_ids: Vec<ResourceId>,
+    solver_options: SolverOptions,
     state: State,
+    cache_generation: usize,
+    with_metadata: bool,
+    metadata: Option<BundleMetadata>,
 }
 
-impl<P, B> GenerateBundlesSync<P, B> {
-    fn new(
-        reg: L10nRegistry<P, B>,
+impl<P, B, F: Family> GenerateBundlesSync<P, B, F> {
+    fn new<R: Into<ResourceId>>(
+        reg: L10nRegistry<P, B, F>,
         locales: std::vec::IntoIter<LanguageIdentifier>,
-        res_ids: Vec<String>,
+        res_ids: Vec<R>,
     ) -> Self {
+        let solver_options = reg.shared.solver_options;
         Self {
             reg,
             locales,
-            res_ids,
+            res_ids: res_ids.into_iter().map(Into::into).collect(),
+            solver_options,
             state: State::Empty,
+            cache_generation: 0,
+            with_metadata: false,
+            metadata: None,
         }
     }
+
+    /// Yield the [`BundleMetadata`](struct.BundleMetadata.html) of each
+    /// bundle along with the bundle.
+    pub fn with_metadata(mut self) -> WithMetadata<Self> {
+        self.with_metadata = true;
+        WithMetadata(self)
+    }
+
+    /// Limit the solutions yielded for each locale, overriding the options
+    /// set with `L10nRegistry::set_solver_options`.
+    pub fn with_solver_options(mut self, solver_options: SolverOptions) -> Self {
+        self.solver_options = solver_options;
+        self
+    }
+
+    fn new_solver(&mut self, locale: &LanguageIdentifier) -> SerialProblemSolver {
+        let lock = self.reg.lock();
+        self.cache_generation = lock.cache_generation();
+        let mut solver = SerialProblemSolver::new(self.res_ids.len(), lock.len());
+        solver.set_options(self.solver_options);
+        for (res_idx, res_id) in self.res_ids.iter().enumerate() {
+            if res_id.is_optional() {
+                solver.set_optional(res_idx);
+            }
+        }
+        self.reg
+            .prefill_solver(&lock, locale, &self.res_ids, &mut solver);
+        solver
+    }
+
+    /// Take the solver out of the state, discarding the results of its tests
+    /// if the cache of any of the sources has been invalidated since.
+    fn take_solver(&mut self) -> SerialProblemSolver {
+        let mut solver = self.state.take_solver();
+        let cache_generation = self.reg.lock().cache_generation();
+        if cache_generation != self.cache_generation {
+            solver.invalidate_cache();
+            self.cache_generation = cache_generation;
+        }
+        solver
+    }
 }
 
-impl<P, B> SyncTester for GenerateBundlesSync<P, B> {
+impl<P, B, F: Family> SyncTester for GenerateBundlesSync<P, B, F> {
     fn test_sync(&self, res_idx: usize, source_idx: usize) -> bool {
         let locale = self.state.get_locale();
-        let res = &self.res_ids[res_idx];
-        self.reg
-            .lock()
-            .source_idx(source_idx)
-            .fetch_file_sync(locale, res, false)
-            .is_some()
+        let res = &self.res_ids[res_idx].value;
+        let lock = self.reg.lock();
+        let source = lock.source_idx(source_idx);
+        let available = source.fetch_file_sync(locale, res, false).is_some();
+        self.reg.record_availability(
+            lock.cache_generation(),
+            locale,
+            res,
+            &source.name,
+            available,
+        );
+        available
     }
 }
 
-impl<P, B> BundleIterator for GenerateBundlesSync<P, B>
+impl<P, B, F> BundleIterator for GenerateBundlesSync<P, B, F>
 where
     P: ErrorReporter,
+    F: Family,
 {
     fn prefetch_sync(&mut self) {
         if let State::Solver { .. } = self.state {
-            let mut solver = self.state.take_solver();
+            let mut solver = self.take_solver();
             if let Err(idx) = solver.try_next(self, true) {
-                self.reg
-                    .shared
-                    .provider
-                    .report_errors(vec![L10nRegistryError::MissingResource {
-                        locale: self.state.get_locale().clone(),
-                        res_id: self.res_ids[idx].clone(),
-                    }]);
+                let error = self
+                    .reg
+                    .lock()
+                    .missing_resource(self.state.get_locale(), &self.res_ids[idx].value);
+                self.reg.shared.provider.report_errors(vec![error]);
             }
             self.state.put_back_solver(solver);
             return;
         }
 
         if let Some(locale) = self.locales.next() {
-            let mut solver = SerialProblemSolver::new(self.res_ids.len(), self.reg.lock().len());
+            let mut solver = self.new_solver(&locale);
             self.state = State::Locale(locale.clone());
             if let Err(idx) = solver.try_next(self, true) {
-                self.reg
-                    .shared
-                    .provider
-                    .report_errors(vec![L10nRegistryError::MissingResource {
-                        locale,
-                        res_id: self.res_ids[idx].clone(),
-                    }]);
+                let error = self
+                    .reg
+                    .lock()
+                    .missing_resource(&locale, &self.res_ids[idx].value);
+                self.reg.shared.provider.report_errors(vec![error]);
             }
             self.state.put_back_solver(solver);
         }
     }
 }
 
-impl<P, B> Iterator for GenerateBundlesSync<P, B>
+impl<P, B, F> Iterator for GenerateBundlesSync<P, B, F>
 where
     P: ErrorReporter,
-    B: BundleAdapter,
+    B: BundleAdapter<F>,
+    F: Family,
 {
-    type Item = Result<FluentBundle, (FluentBundle, Vec<FluentError>)>;
+    type Item = BundleResult<F>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             if let State::Solver { .. } = self.state {
-                let mut solver = self.state.take_solver();
+                let mut solver = self.take_solver();
                 match solver.try_next(self, false) {
                     Ok(Some(order)) => {
                         let locale = self.state.get_locale();
-                        let bundle = self.reg.lock().bundle_from_order(
+                        let lock = self.reg.lock();
+                        let bundle = lock.bundle_from_order(
                             locale.clone(),
                             order,
                             &self.res_ids,
                             &self.reg.shared.provider,
                         );
+                        if self.with_metadata && bundle.is_some() {
+                            self.metadata =
+                                Some(lock.bundle_metadata(locale, order, &self.res_ids));
+                        }
+                        drop(lock);
+                        if bundle.is_none() {
+                            solver.discard_solution();
+                        }
                         self.state.put_back_solver(solver);
                         if bundle.is_some() {
                             return bundle;
@@ -216,20 +290,34 @@ where
                     }
                     Ok(None) => {}
                     Err(idx) => {
-                        self.reg.shared.provider.report_errors(vec![
-                            L10nRegistryError::MissingResource {
-                                locale: self.state.get_locale().clone(),
-                                res_id: self.res_ids[idx].clone(),
-                            },
-                        ]);
+                        let error = self
+                            .reg
+                            .lock()
+                            .missing_resource(self.state.get_locale(), &self.res_ids[idx].value);
+                        self.reg.shared.provider.report_errors(vec![error]);
                     }
                 }
                 self.state = State::Empty;
             }
 
             let locale = self.locales.next()?;
-            let solver = SerialProblemSolver::new(self.res_ids.len(), self.reg.lock().len());
+            let solver = self.new_solver(&locale);
             self.state = State::Solver { locale, solver };
         }
     }
 }
+
+impl<P, B, F> Iterator for WithMetadata<GenerateBundlesSync<P, B, F>>
+where
+    P: ErrorReporter,
+    B: BundleAdapter<F>,
+    F: Family,
+{
+    type Item = (BundleResult<F>, BundleMetadata);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bundle = self.0.next()?;
+        let metadata = self.0.metadata.take().expect("Missing bundle metadata");
+        Some((bundle, metadata))
+    }
+}
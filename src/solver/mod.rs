@@ -5,16 +5,69 @@ pub mod testing;
 pub use parallel::{AsyncTester, ParallelProblemSolver};
 pub use serial::{SerialProblemSolver, SyncTester};
 
+/// How many cells the [`ParallelProblemSolver`] tests ahead of the current
+/// candidate.
+///
+/// Testing more cells at once trades extra I/O for fewer round-trips of
+/// [`AsyncTester::test_async`], which pays off with high-latency fetchers.
+///
+/// [`ParallelProblemSolver`]: struct.ParallelProblemSolver.html
+/// [`AsyncTester::test_async`]: trait.AsyncTester.html#tymethod.test_async
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Lookahead {
+    /// Test only the untested cells of the current candidate.
+    #[default]
+    None,
+    /// Also test the untested cells of the given number of candidates
+    /// following the current one.
+    Candidates(usize),
+    /// Test all the untested cells at once.
+    Eager,
+}
+
+/// Limits the solutions yielded by a solver.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SolverOptions {
+    /// The maximum number of solutions yielded. The solutions passed to
+    /// [`ProblemSolver::discard_solution`] don't count.
+    ///
+    /// [`ProblemSolver::discard_solution`]: struct.ProblemSolver.html#method.discard_solution
+    pub max_solutions: Option<usize>,
+    /// Yield only the first solution, followed by the solutions which differ
+    /// from it by the source of a single resource.
+    ///
+    /// Instead of all the combinations of the sources, this yields, for each
+    /// resource in order, a fallback on each of the other sources providing
+    /// it.
+    pub greedy: bool,
+    /// How many cells are tested ahead of the current candidate. Only the
+    /// [`ParallelProblemSolver`](struct.ParallelProblemSolver.html) tests
+    /// cells ahead.
+    pub lookahead: Lookahead,
+}
+
 pub struct ProblemSolver {
     width: usize,
     depth: usize,
 
+    /// For each resource, whether it may be absent from the solution.
+    optional: Vec<bool>,
     cache: Vec<Vec<Option<bool>>>,
 
     solution: Vec<usize>,
     idx: usize,
 
     dirty: bool,
+
+    options: SolverOptions,
+    /// The number of solutions yielded so far.
+    solutions: usize,
+    /// In the greedy mode, the first solution, which the following ones are
+    /// derived from.
+    greedy_base: Option<Vec<usize>>,
 }
 
 impl ProblemSolver {
@@ -22,32 +75,141 @@ impl ProblemSolver {
         Self {
             width,
             depth,
+            optional: vec![false; width],
             cache: vec![vec![None; depth]; width],
 
             solution: vec![0; width],
             idx: 0,
 
             dirty: false,
+
+            options: SolverOptions::default(),
+            solutions: 0,
+            greedy_base: None,
         }
     }
 }
 
 impl ProblemSolver {
+    /// Mark the resource `res_idx` as optional.
+    ///
+    /// An optional resource which is missing in all sources doesn't prevent
+    /// the solver from finding a solution. Instead, the solution contains
+    /// the index `depth` for that resource, past the last source.
+    pub fn set_optional(&mut self, res_idx: usize) {
+        if !self.optional[res_idx] {
+            self.optional[res_idx] = true;
+            self.cache[res_idx].push(None);
+        }
+    }
+
+    /// Store the known result of testing the cell of the resource `res_idx`
+    /// in the source `source_idx`, so that it isn't tested again.
+    pub fn set_cell(&mut self, res_idx: usize, source_idx: usize, available: bool) {
+        self.cache[res_idx][source_idx] = Some(available);
+    }
+
+    pub fn set_options(&mut self, options: SolverOptions) {
+        self.options = options;
+    }
+
+    /// Whether the maximum number of solutions has been yielded.
+    fn is_exhausted(&self) -> bool {
+        self.options
+            .max_solutions
+            .is_some_and(|max| self.solutions >= max)
+    }
+
+    /// Don't count the last yielded solution towards `max_solutions`, e.g.
+    /// because it couldn't be used.
+    pub fn discard_solution(&mut self) {
+        self.solutions = self.solutions.saturating_sub(1);
+    }
+
+    /// Record that the current solution has been yielded.
+    fn on_solution(&mut self) {
+        self.dirty = true;
+        self.solutions += 1;
+        if self.options.greedy && self.greedy_base.is_none() {
+            self.greedy_base = Some(self.solution.clone());
+            self.idx = 0;
+        }
+    }
+
+    /// In the greedy mode, move to the next source of the current resource,
+    /// or to the next resource, substituting a single resource of the first
+    /// solution at a time.
+    fn try_advance_greedy(&mut self) -> bool {
+        let base = self.greedy_base.as_ref().expect("Missing greedy solution");
+        loop {
+            if self.solution[self.idx] + 1 < self.depth {
+                self.solution[self.idx] += 1;
+                if !self.is_current_cell_missing() {
+                    return true;
+                }
+            } else {
+                self.solution[self.idx] = base[self.idx];
+                if self.idx + 1 >= self.width {
+                    return false;
+                }
+                self.idx += 1;
+            }
+        }
+    }
+
+    /// The index of the last cell in the column of the resource `res_idx`.
+    fn last_source(&self, res_idx: usize) -> usize {
+        if self.optional[res_idx] {
+            self.depth
+        } else {
+            self.depth - 1
+        }
+    }
+
+    /// Resolve the cell standing for the absence of the resource `res_idx`.
+    ///
+    /// An optional resource can only be absent if it's missing in all
+    /// sources, otherwise the solutions would be duplicated.
+    fn test_absent_cell(&mut self, res_idx: usize) -> bool {
+        let cells = &mut self.cache[res_idx];
+        let (absent, sources) = cells.split_last_mut().expect("Optional column is empty");
+        *absent.get_or_insert_with(|| sources.iter().all(|c| *c == Some(false)))
+    }
+
     pub fn bail(&mut self) -> bool {
         if self.try_advance_source() {
-            true
+            self.prune()
         } else {
             self.try_backtrack()
         }
     }
 
-    pub fn has_missing_cell(&self) -> Option<usize> {
-        for res_idx in 0..self.width {
-            if self.cache[res_idx].iter().all(|c| *c == Some(false)) {
-                return Some(res_idx);
+    /// Forget the results of all the tests performed so far, so that the
+    /// cells are tested again when needed.
+    ///
+    /// The solver moves past the last yielded solution and tests the next
+    /// candidate again from its first resource, since the cells it has been
+    /// built from may now be missing. In the greedy mode, the first solution
+    /// is kept and only the substituted cells are tested again.
+    pub fn invalidate_cache(&mut self) {
+        for cell in self.cache.iter_mut().flatten() {
+            *cell = None;
+        }
+        if self.width == 0 || self.depth == 0 || self.greedy_base.is_some() {
+            return;
+        }
+        if self.dirty {
+            if !self.bail() {
+                // The last candidate has been yielded already.
+                return;
             }
+            self.dirty = false;
         }
-        None
+        self.idx = 0;
+    }
+
+    pub fn has_missing_cell(&self) -> Option<usize> {
+        (0..self.width).find(|&res_idx| self.cache[res_idx].iter().all(|c| *c == Some(false)))
     }
 
     fn is_cell_missing(&self, res_idx: usize, source_idx: usize) -> bool {
@@ -82,7 +244,7 @@ impl ProblemSolver {
     }
 
     pub fn try_advance_source(&mut self) -> bool {
-        while self.solution[self.idx] < self.depth - 1 {
+        while self.solution[self.idx] < self.last_source(self.idx) {
             self.solution[self.idx] += 1;
             if !self.is_current_cell_missing() {
                 return true;
@@ -92,7 +254,7 @@ impl ProblemSolver {
     }
 
     pub fn try_backtrack(&mut self) -> bool {
-        while self.solution[self.idx] == self.depth - 1 {
+        while self.solution[self.idx] == self.last_source(self.idx) {
             if self.idx == 0 {
                 return false;
             }
@@ -102,11 +264,35 @@ impl ProblemSolver {
         self.prune()
     }
 
+    /// Move `candidate` to the candidate following it, skipping the cells
+    /// known to be missing.
+    fn try_advance_candidate(&self, candidate: &mut [usize]) -> bool {
+        for res_idx in (0..self.width).rev() {
+            loop {
+                if candidate[res_idx] >= self.last_source(res_idx) {
+                    break;
+                }
+                candidate[res_idx] += 1;
+                if !self.is_cell_missing(res_idx, candidate[res_idx]) {
+                    return true;
+                }
+            }
+            candidate[res_idx] = 0;
+            while self.is_cell_missing(res_idx, candidate[res_idx]) {
+                if candidate[res_idx] >= self.last_source(res_idx) {
+                    return false;
+                }
+                candidate[res_idx] += 1;
+            }
+        }
+        false
+    }
+
     pub fn prune(&mut self) -> bool {
         for i in self.idx + 1..self.width {
             let mut source_idx = 0;
             while self.is_cell_missing(i, source_idx) {
-                if source_idx >= self.depth - 1 {
+                if source_idx >= self.last_source(i) {
                     return false;
                 }
                 source_idx += 1;
@@ -1,4 +1,4 @@
-use super::ProblemSolver;
+use super::{Lookahead, ProblemSolver};
 use std::ops::{Deref, DerefMut};
 
 use futures::ready;
@@ -11,12 +11,14 @@ pub trait AsyncTester {
     fn test_async(&self, query: Vec<(usize, usize)>) -> Self::Result;
 }
 
+type CurrentTest<T> = (<T as AsyncTester>::Result, Vec<(usize, usize)>);
+
 pub struct ParallelProblemSolver<T>
 where
     T: AsyncTester,
 {
     solver: ProblemSolver,
-    current_test: Option<(T::Result, Vec<usize>)>,
+    current_test: Option<CurrentTest<T>>,
 }
 
 impl<T: AsyncTester> Deref for ParallelProblemSolver<T> {
@@ -43,6 +45,15 @@ impl<T: AsyncTester> ParallelProblemSolver<T> {
 }
 
 impl<T: AsyncTester> ParallelProblemSolver<T> {
+    /// Forget the results of all the tests performed so far, dropping the
+    /// test in progress, if any, since its results may be outdated.
+    ///
+    /// See [`ProblemSolver::invalidate_cache`](struct.ProblemSolver.html#method.invalidate_cache).
+    pub fn invalidate_cache(&mut self) {
+        self.current_test = None;
+        self.solver.invalidate_cache();
+    }
+
     pub fn try_generate_complete_candidate(&mut self) -> bool {
         while !self.is_complete() {
             while self.is_current_cell_missing() {
@@ -57,37 +68,76 @@ impl<T: AsyncTester> ParallelProblemSolver<T> {
         true
     }
 
-    fn try_generate_test_query(&mut self) -> Result<(Vec<(usize, usize)>, Vec<usize>), usize> {
-        let mut test_cells = vec![];
-        let query = self
+    fn try_generate_test_query(&mut self) -> Result<Vec<(usize, usize)>, usize> {
+        for res_idx in 0..self.width {
+            if self.solution[res_idx] == self.depth {
+                self.test_absent_cell(res_idx);
+            }
+        }
+        let mut query: Vec<(usize, usize)> = self
             .solution
             .iter()
             .enumerate()
             .filter_map(|(res_idx, source_idx)| {
                 let cell = self.cache[res_idx][*source_idx];
                 match cell {
-                    None => {
-                        test_cells.push(res_idx);
-                        Some(Ok((res_idx, *source_idx)))
-                    }
+                    None => Some(Ok((res_idx, *source_idx))),
                     Some(false) => Some(Err(res_idx)),
                     Some(true) => None,
                 }
             })
             .collect::<Result<_, _>>()?;
-        Ok((query, test_cells))
+        if !query.is_empty() {
+            self.extend_test_query(&mut query);
+        }
+        Ok(query)
+    }
+
+    /// Add the untested cells ahead of the current candidate to `query`, as
+    /// configured by the [`Lookahead`](enum.Lookahead.html) of the solver.
+    fn extend_test_query(&self, query: &mut Vec<(usize, usize)>) {
+        let push_untested = |query: &mut Vec<(usize, usize)>, res_idx: usize, source_idx: usize| {
+            if source_idx < self.depth
+                && self.cache[res_idx][source_idx].is_none()
+                && !query.contains(&(res_idx, source_idx))
+            {
+                query.push((res_idx, source_idx));
+            }
+        };
+        match self.options.lookahead {
+            Lookahead::None => {}
+            Lookahead::Candidates(count) => {
+                let mut candidate = self.solution.clone();
+                for _ in 0..count {
+                    if !self.try_advance_candidate(&mut candidate) {
+                        break;
+                    }
+                    for (res_idx, source_idx) in candidate.iter().enumerate() {
+                        push_untested(query, res_idx, *source_idx);
+                    }
+                }
+            }
+            Lookahead::Eager => {
+                for res_idx in 0..self.width {
+                    for source_idx in 0..self.depth {
+                        push_untested(query, res_idx, source_idx);
+                    }
+                }
+            }
+        }
     }
 
+    /// Store the results of testing the cells of `query`, returning the
+    /// first resource missing in the current candidate.
     fn apply_test_result(
         &mut self,
         resources: Vec<bool>,
-        testing_cells: Vec<usize>,
+        query: Vec<(usize, usize)>,
     ) -> Result<(), usize> {
         let mut first_missing = None;
-        for (result, res_idx) in resources.into_iter().zip(testing_cells) {
-            let source_idx = self.solution[res_idx];
+        for (result, (res_idx, source_idx)) in resources.into_iter().zip(query) {
             self.cache[res_idx][source_idx] = Some(result);
-            if !result && first_missing.is_none() {
+            if !result && first_missing.is_none() && self.solution[res_idx] == source_idx {
                 first_missing = Some(res_idx);
             }
         }
@@ -107,9 +157,12 @@ impl<T: AsyncTester> ParallelProblemSolver<T> {
     where
         <T as AsyncTester>::Result: Unpin,
     {
-        if self.width == 0 || self.depth == 0 {
+        if self.width == 0 || self.depth == 0 || self.is_exhausted() {
             return Ok(None).into();
         }
+        if self.greedy_base.is_some() {
+            return self.poll_next_greedy(cx, tester, prefetch).map(Ok);
+        }
 
         'outer: loop {
             if let Some((test, testing_cells)) = &mut self.current_test {
@@ -132,7 +185,7 @@ impl<T: AsyncTester> ParallelProblemSolver<T> {
                 } else {
                     self.current_test = None;
                     if !prefetch {
-                        self.dirty = true;
+                        self.on_solution();
                     }
                     return Ok(Some(self.solution.clone())).into();
                 }
@@ -149,8 +202,14 @@ impl<T: AsyncTester> ParallelProblemSolver<T> {
                 }
                 while self.try_generate_complete_candidate() {
                     match self.try_generate_test_query() {
-                        Ok((query, testing_cells)) => {
-                            self.current_test = Some((tester.test_async(query), testing_cells));
+                        Ok(query) if query.is_empty() => {
+                            if !prefetch {
+                                self.on_solution();
+                            }
+                            return Ok(Some(self.solution.clone())).into();
+                        }
+                        Ok(query) => {
+                            self.current_test = Some((tester.test_async(query.clone()), query));
                             continue 'outer;
                         }
                         Err(res_idx) => {
@@ -170,10 +229,212 @@ impl<T: AsyncTester> ParallelProblemSolver<T> {
             }
         }
     }
+
+    fn poll_next_greedy(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        tester: &T,
+        prefetch: bool,
+    ) -> std::task::Poll<Option<Vec<usize>>>
+    where
+        <T as AsyncTester>::Result: Unpin,
+    {
+        loop {
+            if let Some((test, testing_cells)) = &mut self.current_test {
+                let set = ready!(Pin::new(test).poll(cx));
+                let testing_cells = testing_cells.clone();
+                self.current_test = None;
+                if self.apply_test_result(set, testing_cells).is_err() {
+                    self.dirty = true;
+                    continue;
+                }
+            } else {
+                if self.dirty {
+                    if !self.try_advance_greedy() {
+                        return None.into();
+                    }
+                    self.dirty = false;
+                }
+                let res_idx = self.idx;
+                let source_idx = self.solution[res_idx];
+                if self.cache[res_idx][source_idx].is_none() {
+                    let query = vec![(res_idx, source_idx)];
+                    self.current_test = Some((tester.test_async(query.clone()), query));
+                    continue;
+                }
+                if self.is_current_cell_missing() {
+                    self.dirty = true;
+                    continue;
+                }
+            }
+            if !prefetch {
+                self.on_solution();
+            }
+            return Some(self.solution.clone()).into();
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::solver::SolverOptions;
+    use futures::future::{ready, Ready};
+    use std::cell::Cell;
+
+    struct TestTester(Vec<Vec<bool>>);
+
+    impl AsyncTester for TestTester {
+        type Result = Ready<Vec<bool>>;
+
+        fn test_async(&self, query: Vec<(usize, usize)>) -> Self::Result {
+            ready(
+                query
+                    .into_iter()
+                    .map(|(res_idx, source_idx)| self.0[res_idx][source_idx])
+                    .collect(),
+            )
+        }
+    }
+
+    fn collect_solutions<T>(mut solver: ParallelProblemSolver<T>, tester: &T) -> Vec<Vec<usize>>
+    where
+        T: AsyncTester,
+        T::Result: Unpin,
+    {
+        let mut solutions = vec![];
+        futures::executor::block_on(futures::future::poll_fn(|cx| {
+            while let std::task::Poll::Ready(result) =
+                Pin::new(&mut solver).try_poll_next(cx, tester, false)
+            {
+                match result.unwrap() {
+                    Some(solution) => solutions.push(solution),
+                    None => return std::task::Poll::Ready(()),
+                }
+            }
+            std::task::Poll::Pending
+        }));
+        solutions
+    }
+
+    #[test]
+    fn parallel_problem_solver_optional() {
+        let tester = TestTester(vec![vec![true, true], vec![false, false]]);
+        let mut solver = ParallelProblemSolver::new(2, 2);
+        solver.set_optional(1);
+
+        let mut solutions = vec![];
+        futures::executor::block_on(futures::future::poll_fn(|cx| {
+            while let std::task::Poll::Ready(result) =
+                Pin::new(&mut solver).try_poll_next(cx, &tester, false)
+            {
+                match result.unwrap() {
+                    Some(solution) => solutions.push(solution),
+                    None => return std::task::Poll::Ready(()),
+                }
+            }
+            std::task::Poll::Pending
+        }));
+        assert_eq!(solutions, vec![vec![0, 2], vec![1, 2]]);
+    }
+
+    #[test]
+    fn parallel_problem_solver_greedy() {
+        let tester = TestTester(vec![
+            vec![true, true, true],
+            vec![false, true, false],
+            vec![false, true, true],
+        ]);
+        let mut solver = ParallelProblemSolver::new(3, 3);
+        solver.set_options(SolverOptions {
+            max_solutions: Some(3),
+            greedy: true,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            collect_solutions(solver, &tester),
+            vec![vec![0, 1, 1], vec![1, 1, 1], vec![2, 1, 1]]
+        );
+    }
+
+    struct CountingTester(TestTester, Cell<usize>);
+
+    impl AsyncTester for CountingTester {
+        type Result = Ready<Vec<bool>>;
+
+        fn test_async(&self, query: Vec<(usize, usize)>) -> Self::Result {
+            self.1.set(self.1.get() + 1);
+            self.0.test_async(query)
+        }
+    }
+
+    #[test]
+    fn parallel_problem_solver_lookahead() {
+        let values = vec![
+            vec![false, true, true],
+            vec![true, false, true],
+            vec![false, false, true],
+        ];
+        let expected = vec![vec![1, 0, 2], vec![1, 2, 2], vec![2, 0, 2], vec![2, 2, 2]];
+
+        let mut round_trips = vec![];
+        for lookahead in &[Lookahead::None, Lookahead::Candidates(2), Lookahead::Eager] {
+            let tester = CountingTester(TestTester(values.clone()), Cell::new(0));
+            let mut solver = ParallelProblemSolver::new(3, 3);
+            solver.set_options(SolverOptions {
+                lookahead: *lookahead,
+                ..Default::default()
+            });
+            assert_eq!(collect_solutions(solver, &tester), expected);
+            round_trips.push(tester.1.get());
+        }
+        assert!(round_trips[0] > round_trips[1]);
+        assert!(round_trips[1] > round_trips[2]);
+        assert_eq!(round_trips[2], 1);
+    }
+
+    /// A tester whose tests never resolve while `pending` is set.
+    struct PendingTester {
+        tester: TestTester,
+        pending: Cell<bool>,
+    }
+
+    impl AsyncTester for PendingTester {
+        type Result = Pin<Box<dyn Future<Output = Vec<bool>>>>;
+
+        fn test_async(&self, query: Vec<(usize, usize)>) -> Self::Result {
+            if self.pending.get() {
+                Box::pin(futures::future::pending())
+            } else {
+                Box::pin(self.tester.test_async(query))
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_problem_solver_invalidate_cache() {
+        let tester = PendingTester {
+            tester: TestTester(vec![vec![false, true], vec![true, true]]),
+            pending: Cell::new(true),
+        };
+        let mut solver = ParallelProblemSolver::new(2, 2);
+        futures::executor::block_on(futures::future::poll_fn(|cx| {
+            assert!(Pin::new(&mut solver)
+                .try_poll_next(cx, &tester, false)
+                .is_pending());
+            std::task::Poll::Ready(())
+        }));
+
+        // The pending test is dropped along with the cache.
+        solver.invalidate_cache();
+        tester.pending.set(false);
+        assert_eq!(
+            collect_solutions(solver, &tester),
+            vec![vec![1, 0], vec![1, 1]]
+        );
+    }
+
     #[test]
     fn problem_solver() {
         // let keys = vec!["key1.ftl", "key2.ftl"];
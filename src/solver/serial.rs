@@ -34,6 +34,9 @@ impl SerialProblemSolver {
     {
         let res_idx = self.idx;
         let source_idx = self.solution[res_idx];
+        if source_idx == self.depth {
+            return self.test_absent_cell(res_idx);
+        }
         let cell = &mut self.cache[res_idx][source_idx];
         *cell.get_or_insert_with(|| tester.test_sync(res_idx, source_idx))
     }
@@ -42,9 +45,12 @@ impl SerialProblemSolver {
     where
         T: SyncTester,
     {
-        if self.width == 0 || self.depth == 0 {
+        if self.width == 0 || self.depth == 0 || self.is_exhausted() {
             return Ok(None);
         }
+        if self.greedy_base.is_some() {
+            return Ok(self.try_next_greedy(tester, prefetch));
+        }
         if self.dirty {
             if !self.bail() {
                 return Ok(None);
@@ -64,7 +70,7 @@ impl SerialProblemSolver {
             }
             if self.is_complete() {
                 if !prefetch {
-                    self.dirty = true;
+                    self.on_solution();
                 }
                 return Ok(Some(&self.solution));
             }
@@ -73,10 +79,53 @@ impl SerialProblemSolver {
             }
         }
     }
+
+    fn try_next_greedy<T>(&mut self, tester: &T, prefetch: bool) -> Option<&[usize]>
+    where
+        T: SyncTester,
+    {
+        loop {
+            if self.dirty {
+                if !self.try_advance_greedy() {
+                    return None;
+                }
+                self.dirty = false;
+            }
+            if self.test_current_cell(tester) {
+                if !prefetch {
+                    self.on_solution();
+                }
+                return Some(&self.solution);
+            }
+            self.dirty = true;
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::solver::SolverOptions;
+
+    struct TestTester(Vec<Vec<bool>>);
+
+    impl SyncTester for TestTester {
+        fn test_sync(&self, res_idx: usize, source_idx: usize) -> bool {
+            self.0[res_idx][source_idx]
+        }
+    }
+
+    fn collect_solutions(
+        solver: &mut SerialProblemSolver,
+        tester: &TestTester,
+    ) -> Result<Vec<Vec<usize>>, usize> {
+        let mut result = vec![];
+        while let Some(solution) = solver.try_next(tester, false)? {
+            result.push(solution.to_vec());
+        }
+        Ok(result)
+    }
+
     #[test]
     fn problem_solver() {
         // let keys = vec!["key1.ftl", "key2.ftl"];
@@ -85,4 +134,116 @@ mod tests {
 
         // let ps = ProblemSolver::new(keys.len(), sources.len(), &foo);
     }
+
+    #[test]
+    fn problem_solver_optional_missing() {
+        let tester = TestTester(vec![vec![true, true], vec![false, false]]);
+
+        let mut solver = SerialProblemSolver::new(2, 2);
+        assert_eq!(collect_solutions(&mut solver, &tester), Err(1));
+
+        let mut solver = SerialProblemSolver::new(2, 2);
+        solver.set_optional(1);
+        assert_eq!(
+            collect_solutions(&mut solver, &tester),
+            Ok(vec![vec![0, 2], vec![1, 2]])
+        );
+    }
+
+    #[test]
+    fn problem_solver_max_solutions() {
+        let tester = TestTester(vec![vec![true, true], vec![true, true]]);
+
+        let mut solver = SerialProblemSolver::new(2, 2);
+        solver.set_options(SolverOptions {
+            max_solutions: Some(3),
+            greedy: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            collect_solutions(&mut solver, &tester),
+            Ok(vec![vec![0, 0], vec![0, 1], vec![1, 0]])
+        );
+    }
+
+    #[test]
+    fn problem_solver_discard_solution() {
+        let tester = TestTester(vec![vec![true, true], vec![true, true]]);
+
+        let mut solver = SerialProblemSolver::new(2, 2);
+        solver.set_options(SolverOptions {
+            max_solutions: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(solver.try_next(&tester, false), Ok(Some(&[0, 0][..])));
+        solver.discard_solution();
+        assert_eq!(solver.try_next(&tester, false), Ok(Some(&[0, 1][..])));
+        assert_eq!(solver.try_next(&tester, false), Ok(None));
+    }
+
+    #[test]
+    fn problem_solver_greedy() {
+        let tester = TestTester(vec![
+            vec![true, true, true],
+            vec![false, true, false],
+            vec![false, true, true],
+        ]);
+
+        let mut solver = SerialProblemSolver::new(3, 3);
+        solver.set_options(SolverOptions {
+            max_solutions: None,
+            greedy: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            collect_solutions(&mut solver, &tester),
+            Ok(vec![
+                vec![0, 1, 1],
+                vec![1, 1, 1],
+                vec![2, 1, 1],
+                vec![0, 1, 2],
+            ])
+        );
+
+        let mut solver = SerialProblemSolver::new(3, 3);
+        solver.set_options(SolverOptions {
+            max_solutions: Some(2),
+            greedy: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            collect_solutions(&mut solver, &tester),
+            Ok(vec![vec![0, 1, 1], vec![1, 1, 1]])
+        );
+    }
+
+    #[test]
+    fn problem_solver_invalidate_cache() {
+        let tester = TestTester(vec![vec![true, true], vec![true, true]]);
+
+        let mut solver = SerialProblemSolver::new(2, 2);
+        assert_eq!(solver.try_next(&tester, false), Ok(Some(&[0, 0][..])));
+
+        // The first resource is now missing in the first source, so the
+        // candidates built from it are skipped.
+        solver.invalidate_cache();
+        let tester = TestTester(vec![vec![false, true], vec![true, true]]);
+        assert_eq!(
+            collect_solutions(&mut solver, &tester),
+            Ok(vec![vec![1, 0], vec![1, 1]])
+        );
+    }
+
+    #[test]
+    fn problem_solver_optional_available() {
+        let tester = TestTester(vec![vec![false, true], vec![true, false]]);
+
+        let mut solver = SerialProblemSolver::new(2, 2);
+        solver.set_optional(0);
+        solver.set_optional(1);
+        assert_eq!(
+            collect_solutions(&mut solver, &tester),
+            Ok(vec![vec![1, 0]])
+        );
+    }
 }
@@ -0,0 +1,304 @@
+use rustc_hash::FxHashMap;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// Limits applied to the cache of resources of a [`FileSource`].
+///
+/// Loaded and missing resources are evicted, least recently used first,
+/// whenever one of the limits is exceeded. Resources which are being loaded
+/// are never evicted.
+///
+/// [`FileSource`]: struct.FileSource.html
+#[derive(PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CachePolicy {
+    /// The maximum number of loaded and missing resources kept in the
+    /// cache.
+    pub max_entries: Option<usize>,
+    /// The maximum approximate size, in bytes, of the loaded resources kept
+    /// in the cache, based on the length of their sources.
+    pub max_bytes: Option<usize>,
+    /// How long a resource is remembered as missing before it is fetched
+    /// again.
+    pub missing_ttl: Option<Duration>,
+}
+
+/// A status of a resource stored in a [`ResourceCache`].
+pub(crate) trait CacheStatus: Clone {
+    /// The size of the resource if it's loaded.
+    fn loaded_size(&self) -> Option<usize>;
+
+    fn is_missing(&self) -> bool;
+
+    /// Whether the resource can be evicted from the cache, i.e. it's
+    /// either loaded or missing.
+    fn is_resolved(&self) -> bool {
+        self.is_missing() || self.loaded_size().is_some()
+    }
+}
+
+struct CacheEntry<S> {
+    status: S,
+    last_used: u64,
+    created: Instant,
+}
+
+/// A map of resource statuses keyed by their path, enforcing a
+/// [`CachePolicy`](struct.CachePolicy.html).
+pub(crate) struct ResourceCache<S> {
+    policy: CachePolicy,
+    entries: FxHashMap<String, CacheEntry<S>>,
+    /// The paths of the resolved entries, keyed by their `last_used` tick,
+    /// from the least to the most recently used.
+    lru: BTreeMap<u64, String>,
+    tick: u64,
+    loaded_bytes: usize,
+    /// When the first of the missing resources expires, if any.
+    next_expiry: Option<Instant>,
+}
+
+impl<S: CacheStatus> ResourceCache<S> {
+    pub fn new(policy: CachePolicy) -> Self {
+        Self {
+            policy,
+            entries: FxHashMap::default(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            loaded_bytes: 0,
+            next_expiry: None,
+        }
+    }
+
+    pub fn policy(&self) -> &CachePolicy {
+        &self.policy
+    }
+
+    fn is_expired(&self, entry: &CacheEntry<S>) -> bool {
+        match self.policy.missing_ttl {
+            Some(ttl) => entry.status.is_missing() && entry.created.elapsed() >= ttl,
+            None => false,
+        }
+    }
+
+    /// Return the status of the resource at `path` without marking it as
+    /// used.
+    pub fn peek(&self, path: &str) -> Option<&S> {
+        self.entries
+            .get(path)
+            .filter(|entry| !self.is_expired(entry))
+            .map(|entry| &entry.status)
+    }
+
+    /// Return the status of the resource at `path` and mark it as the most
+    /// recently used.
+    pub fn get(&mut self, path: &str) -> Option<S> {
+        let expired = self.entries.get(path).map(|entry| self.is_expired(entry))?;
+        if expired {
+            self.remove(path);
+            return None;
+        }
+        self.tick += 1;
+        let entry = self.entries.get_mut(path)?;
+        if let Some(path) = self.lru.remove(&entry.last_used) {
+            self.lru.insert(self.tick, path);
+        }
+        entry.last_used = self.tick;
+        Some(entry.status.clone())
+    }
+
+    /// Iterate over the statuses of the resources in the cache, without
+    /// marking them as used.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &S)> {
+        self.entries
+            .iter()
+            .filter(move |(_, entry)| !self.is_expired(entry))
+            .map(|(path, entry)| (path.as_str(), &entry.status))
+    }
+
+    pub fn insert(&mut self, path: String, status: S) {
+        self.remove_expired();
+        self.remove(&path);
+        self.loaded_bytes += status.loaded_size().unwrap_or(0);
+        self.tick += 1;
+        if status.is_resolved() {
+            self.lru.insert(self.tick, path.clone());
+        }
+        if let Some(ttl) = self.policy.missing_ttl.filter(|_| status.is_missing()) {
+            let expiry = Instant::now() + ttl;
+            self.next_expiry = Some(self.next_expiry.map_or(expiry, |next| next.min(expiry)));
+        }
+        self.entries.insert(
+            path.clone(),
+            CacheEntry {
+                status,
+                last_used: self.tick,
+                created: Instant::now(),
+            },
+        );
+        self.evict(&path);
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<S> {
+        let entry = self.entries.remove(path)?;
+        if entry.status.is_resolved() {
+            self.lru.remove(&entry.last_used);
+        }
+        self.loaded_bytes -= entry.status.loaded_size().unwrap_or(0);
+        Some(entry.status)
+    }
+
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        let removed: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|path| !f(path))
+            .cloned()
+            .collect();
+        for path in removed {
+            self.remove(&path);
+        }
+    }
+
+    /// Remove the missing resources whose `missing_ttl` has expired.
+    fn remove_expired(&mut self) {
+        if self
+            .next_expiry
+            .map_or(true, |expiry| expiry > Instant::now())
+        {
+            return;
+        }
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| self.is_expired(entry))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in expired {
+            self.remove(&path);
+        }
+        self.next_expiry = self.policy.missing_ttl.and_then(|ttl| {
+            self.entries
+                .values()
+                .filter(|entry| entry.status.is_missing())
+                .map(|entry| entry.created + ttl)
+                .min()
+        });
+    }
+
+    fn is_over_limit(&self) -> bool {
+        self.policy
+            .max_entries
+            .is_some_and(|max| self.lru.len() > max)
+            || self
+                .policy
+                .max_bytes
+                .is_some_and(|max| self.loaded_bytes > max)
+    }
+
+    /// Evict the least recently used resolved resources, other than `keep`,
+    /// until the cache fits within the limits of the policy.
+    fn evict(&mut self, keep: &str) {
+        while self.is_over_limit() {
+            let lru = self.lru.values().find(|path| *path != keep).cloned();
+            match lru {
+                Some(path) => {
+                    self.remove(&path);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum TestStatus {
+        Loading,
+        Missing,
+        Loaded(usize),
+    }
+
+    impl CacheStatus for TestStatus {
+        fn loaded_size(&self) -> Option<usize> {
+            match self {
+                Self::Loaded(size) => Some(*size),
+                Self::Loading | Self::Missing => None,
+            }
+        }
+
+        fn is_missing(&self) -> bool {
+            *self == Self::Missing
+        }
+    }
+
+    #[test]
+    fn cache_max_entries() {
+        let mut cache = ResourceCache::new(CachePolicy {
+            max_entries: Some(2),
+            ..Default::default()
+        });
+
+        cache.insert("loading".to_string(), TestStatus::Loading);
+        cache.insert("a".to_string(), TestStatus::Loaded(1));
+        cache.insert("b".to_string(), TestStatus::Loaded(1));
+        assert!(cache.get("a").is_some());
+
+        // Missing resources count towards the limit.
+        cache.insert("missing".to_string(), TestStatus::Missing);
+        assert!(cache.peek("a").is_some());
+        assert!(cache.peek("b").is_none());
+        assert_eq!(cache.peek("missing"), Some(&TestStatus::Missing));
+
+        assert!(cache.get("missing").is_some());
+        cache.insert("c".to_string(), TestStatus::Loaded(1));
+        assert!(cache.peek("a").is_none());
+        assert!(cache.peek("c").is_some());
+        assert_eq!(cache.peek("missing"), Some(&TestStatus::Missing));
+
+        // Resources which are being loaded are never evicted.
+        assert_eq!(cache.peek("loading"), Some(&TestStatus::Loading));
+    }
+
+    #[test]
+    fn cache_max_bytes() {
+        let mut cache = ResourceCache::new(CachePolicy {
+            max_bytes: Some(10),
+            ..Default::default()
+        });
+
+        cache.insert("a".to_string(), TestStatus::Loaded(4));
+        cache.insert("b".to_string(), TestStatus::Loaded(4));
+        cache.insert("c".to_string(), TestStatus::Loaded(4));
+        assert!(cache.peek("a").is_none());
+        assert!(cache.peek("b").is_some());
+        assert!(cache.peek("c").is_some());
+
+        cache.insert("d".to_string(), TestStatus::Loaded(20));
+        assert!(cache.peek("b").is_none());
+        assert!(cache.peek("c").is_none());
+        assert!(cache.peek("d").is_some());
+    }
+
+    #[test]
+    fn cache_missing_ttl() {
+        let mut cache = ResourceCache::new(CachePolicy {
+            missing_ttl: Some(Duration::from_secs(0)),
+            ..Default::default()
+        });
+
+        cache.insert("a".to_string(), TestStatus::Loaded(4));
+        cache.insert("missing".to_string(), TestStatus::Missing);
+        assert!(cache.peek("a").is_some());
+        assert!(cache.peek("missing").is_none());
+        assert!(cache.get("missing").is_none());
+    }
+}
@@ -0,0 +1,100 @@
+use super::{FileSource, FileSourceOptions, PathTemplate, SourceIndex};
+use crate::family::{Family, IntoFetcher};
+
+use unic_langid::LanguageIdentifier;
+
+/// The configuration of a [`FileSource`], without its fetcher and the state
+/// of its cache, which can be persisted and turned back into a source with
+/// [`FileSource::from_description`].
+///
+/// With the `serde` feature, the description is serializable. The `index`
+/// and the `options` may be omitted.
+///
+/// [`FileSource`]: struct.FileSource.html
+/// [`FileSource::from_description`]: struct.FileSource.html#method.from_description
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileSourceDescription {
+    pub name: String,
+    pub locales: Vec<LanguageIdentifier>,
+    pub pre_path: PathTemplate,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub index: Option<SourceIndex>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub options: FileSourceOptions,
+}
+
+impl<F: Family> FileSource<F> {
+    pub fn describe(&self) -> FileSourceDescription {
+        FileSourceDescription {
+            name: self.name.clone(),
+            locales: self.locales.clone(),
+            pre_path: self.pre_path.clone(),
+            index: self.index.clone(),
+            options: self.options.clone(),
+        }
+    }
+
+    /// Create a `FileSource` from its `description`, fetching the resources
+    /// with `fetcher`.
+    pub fn from_description(
+        description: FileSourceDescription,
+        fetcher: impl IntoFetcher<F>,
+    ) -> Self {
+        let FileSourceDescription {
+            name,
+            locales,
+            pre_path,
+            index,
+            options,
+        } = description;
+        match index {
+            Some(index) => {
+                Self::new_with_source_index(name, locales, pre_path, options, fetcher, index)
+            }
+            None => Self::new(name, locales, pre_path, options, fetcher),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_source_description_serde() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let json = r#"{
+            "name": "browser",
+            "locales": ["en-US", "pl"],
+            "pre_path": "browser/{locale}/",
+            "index": {"en-US": ["menu.ftl"], "pl": []},
+            "options": {"priority": 1, "cache": {"max_entries": 10}}
+        }"#;
+        let description: FileSourceDescription = serde_json::from_str(json).unwrap();
+        assert_eq!(description.locales[0], en_us);
+        assert_eq!(description.options.priority, 1);
+        assert_eq!(description.options.cache.max_entries, Some(10));
+        assert!(!description.options.allow_override);
+
+        let index = description.index.as_ref().unwrap();
+        assert!(index.contains(&en_us, "menu.ftl"));
+        assert_eq!(index.locales().count(), 2);
+
+        let json = serde_json::to_string(&description).unwrap();
+        assert_eq!(
+            serde_json::from_str::<FileSourceDescription>(&json).unwrap(),
+            description
+        );
+
+        let description: FileSourceDescription = serde_json::from_str(
+            r#"{"name": "app", "locales": ["en-US"], "pre_path": "app/{locale}/"}"#,
+        )
+        .unwrap();
+        assert_eq!(description.index, None);
+        assert_eq!(description.options, FileSourceOptions::default());
+    }
+}
@@ -6,6 +6,10 @@ use std::io;
 /// `String`. [`FileSource`] handles the conversion from string representation
 /// into `FluentResource`.
 ///
+/// A missing resource should be signaled with an `io::ErrorKind::NotFound`
+/// error. Any other error is reported as an
+/// [`L10nRegistryError::IoError`](../errors/enum.L10nRegistryError.html).
+///
 /// [`FileSource`]: source/struct.FileSource.html
 #[async_trait(?Send)]
 pub trait FileFetcher {
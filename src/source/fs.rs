@@ -0,0 +1,306 @@
+use super::{FileFetcher, PathTemplate, SourceIndex};
+use async_trait::async_trait;
+#[cfg(not(feature = "tokio-io"))]
+use futures::channel::oneshot;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(not(feature = "tokio-io"))]
+use std::sync::{mpsc, Mutex, OnceLock};
+use unic_langid::LanguageIdentifier;
+
+/// A [`FileFetcher`] which reads resources from the filesystem, resolving
+/// the requested paths against a base directory.
+///
+/// With the `tokio-io` feature enabled, asynchronous fetches are performed
+/// using `tokio::fs` and have to be polled from within a tokio runtime.
+/// Otherwise, asynchronous fetches read the files on a blocking thread
+/// shared by all the `FsFileFetcher`s, so that they don't block the
+/// executor.
+///
+/// With the `sync` feature, it's also a `SendFileFetcher`.
+///
+/// [`FileFetcher`]: ../trait.FileFetcher.html
+#[derive(Debug, Clone)]
+pub struct FsFileFetcher {
+    root: PathBuf,
+}
+
+impl FsFileFetcher {
+    /// Create a `FsFileFetcher` rooted at `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The directory the requested paths are resolved against.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn resolve_path(&self, path: &str) -> PathBuf {
+        self.root.join(path)
+    }
+}
+
+#[async_trait(?Send)]
+impl FileFetcher for FsFileFetcher {
+    fn fetch_sync(&self, path: &str) -> io::Result<String> {
+        std::fs::read_to_string(self.resolve_path(path))
+    }
+
+    async fn fetch(&self, path: &str) -> io::Result<String> {
+        read_file(self.resolve_path(path)).await
+    }
+}
+
+#[cfg(feature = "sync")]
+#[async_trait]
+impl crate::sync::SendFileFetcher for FsFileFetcher {
+    fn fetch_sync(&self, path: &str) -> io::Result<String> {
+        std::fs::read_to_string(self.resolve_path(path))
+    }
+
+    async fn fetch(&self, path: &str) -> io::Result<String> {
+        read_file(self.resolve_path(path)).await
+    }
+}
+
+#[cfg(feature = "tokio-io")]
+async fn read_file(path: PathBuf) -> io::Result<String> {
+    tokio::fs::read_to_string(path).await
+}
+
+/// The files to read on the blocking thread, along with the senders of the
+/// results.
+#[cfg(not(feature = "tokio-io"))]
+type ReadQueue = mpsc::Sender<(PathBuf, oneshot::Sender<io::Result<String>>)>;
+
+#[cfg(not(feature = "tokio-io"))]
+static READ_QUEUE: OnceLock<Mutex<ReadQueue>> = OnceLock::new();
+
+/// Read the file at `path` on the blocking thread, which is started on the
+/// first read.
+#[cfg(not(feature = "tokio-io"))]
+async fn read_file(path: PathBuf) -> io::Result<String> {
+    let queue = READ_QUEUE.get_or_init(|| {
+        let (queue, reads) = mpsc::channel::<(PathBuf, oneshot::Sender<io::Result<String>>)>();
+        // If the thread can't be spawned, the reads are dropped and the
+        // fetches fail below.
+        let _ = std::thread::Builder::new()
+            .name("l10nregistry-fs".to_string())
+            .spawn(move || {
+                for (path, sender) in reads {
+                    let _ = sender.send(std::fs::read_to_string(path));
+                }
+            });
+        Mutex::new(queue)
+    });
+    let (sender, receiver) = oneshot::channel();
+    let queued = queue
+        .lock()
+        .is_ok_and(|queue| queue.send((path, sender)).is_ok());
+    let unavailable = || io::Error::other("The thread reading the files is unavailable.");
+    if !queued {
+        return Err(unavailable());
+    }
+    receiver.await.unwrap_or_else(|_| Err(unavailable()))
+}
+
+/// Build the [`SourceIndex`] of a source whose resources are stored in the
+/// directory `root`, by walking the directory of each of the `locales`.
+///
+/// The directory of a locale is the deepest one containing all the paths
+/// matching `pre_path`. Only `.ftl` files are indexed. Locales whose
+/// directory doesn't exist are left out of the index. The result can be stored as a manifest with
+/// [`SourceIndex::to_manifest`] and shipped along with the resources.
+///
+/// [`SourceIndex`]: ../struct.SourceIndex.html
+/// [`SourceIndex::to_manifest`]: ../struct.SourceIndex.html#method.to_manifest
+pub fn build_index<P: AsRef<Path>>(
+    root: P,
+    pre_path: &PathTemplate,
+    locales: &[LanguageIdentifier],
+) -> io::Result<SourceIndex> {
+    fn walk(dir: &Path, prefix: &str, result: &mut Vec<String>) -> io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = format!("{}{}", prefix, name);
+            if entry.file_type()?.is_dir() {
+                walk(&entry.path(), &format!("{}/", path), result)?;
+            } else if name.ends_with(".ftl") {
+                result.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut index = SourceIndex::new();
+    for locale in locales {
+        let dir = pre_path.base_dir(locale);
+        let mut paths = vec![];
+        match walk(&root.as_ref().join(&dir), &dir, &mut paths) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            result => result?,
+        }
+        for path in paths {
+            if let Some(res_id) = pre_path.match_path(locale, &path) {
+                index.insert(locale.clone(), res_id.to_string());
+            }
+        }
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::L10nRegistryError;
+    use crate::family::Local;
+    use crate::source::FileSource;
+    use std::fs;
+
+    fn get_test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("l10nregistry-fs-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("en-US")).unwrap();
+        fs::write(dir.join("en-US/main.ftl"), "key = Value\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn fs_fetcher_fetch_sync() {
+        let dir = get_test_dir("sync");
+        let fetcher = FsFileFetcher::new(&dir);
+
+        assert_eq!(
+            fetcher.fetch_sync("en-US/main.ftl").unwrap(),
+            "key = Value\n"
+        );
+        assert_eq!(
+            fetcher.fetch_sync("en-US/missing.ftl").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fs_build_index() {
+        let dir = get_test_dir("index");
+        fs::create_dir_all(dir.join("en-US/panels")).unwrap();
+        fs::write(dir.join("en-US/panels/about.ftl"), "about = About\n").unwrap();
+        fs::write(dir.join("en-US/README.md"), "").unwrap();
+
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let locales = vec![en_us.clone(), "pl".parse().unwrap()];
+        let index = build_index(&dir, &"{locale}/".parse().unwrap(), &locales).unwrap();
+        assert_eq!(index.to_manifest(), "[en-US]\nmain.ftl\npanels/about.ftl\n");
+
+        let pre_path = "{language}/{res_id}.ftl".parse().unwrap();
+        fs::create_dir_all(dir.join("en")).unwrap();
+        fs::write(dir.join("en/main.ftl"), "key = Value\n").unwrap();
+        assert_eq!(
+            build_index(&dir, &pre_path, std::slice::from_ref(&en_us))
+                .unwrap()
+                .to_manifest(),
+            "[en-US]\nmain\n"
+        );
+
+        fs::write(dir.join("manifest.txt"), index.to_manifest()).unwrap();
+        let source: FileSource = FileSource::new_with_manifest(
+            "app".to_string(),
+            locales,
+            "{locale}/".parse().unwrap(),
+            Default::default(),
+            FsFileFetcher::new(&dir),
+            "manifest.txt",
+        )
+        .unwrap();
+        assert_eq!(source.get_index(), Some(&index));
+        assert_eq!(source.has_file(&en_us, "panels/about.ftl"), Some(true));
+        assert_eq!(source.has_file(&en_us, "README.md"), Some(false));
+        assert!(matches!(
+            FileSource::<Local>::new_with_manifest(
+                "app".to_string(),
+                vec![en_us],
+                "{locale}/".parse().unwrap(),
+                Default::default(),
+                FsFileFetcher::new(&dir),
+                "missing.txt",
+            ),
+            Err(L10nRegistryError::IoError {
+                kind: io::ErrorKind::NotFound,
+                ..
+            })
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(not(feature = "tokio-io"))]
+    #[test]
+    fn fs_fetcher_fetch() {
+        let dir = get_test_dir("async");
+        let fetcher = FsFileFetcher::new(&dir);
+
+        futures::executor::block_on(async {
+            assert_eq!(
+                fetcher.fetch("en-US/main.ftl").await.unwrap(),
+                "key = Value\n"
+            );
+            assert_eq!(
+                fetcher.fetch("en-US/missing.ftl").await.unwrap_err().kind(),
+                io::ErrorKind::NotFound
+            );
+        });
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn fs_fetcher_send() {
+        use crate::sync::{FileSource, SendFileFetcher};
+
+        let dir = get_test_dir("send");
+        let fetcher = FsFileFetcher::new(&dir);
+        assert_eq!(
+            SendFileFetcher::fetch_sync(&fetcher, "en-US/main.ftl").unwrap(),
+            "key = Value\n"
+        );
+
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let source = FileSource::new(
+            "app".to_string(),
+            vec![en_us.clone()],
+            "{locale}/".parse().unwrap(),
+            Default::default(),
+            fetcher,
+        );
+        std::thread::spawn(move || {
+            assert!(source.fetch_file_sync(&en_us, "main.ftl", false).is_some());
+        })
+        .join()
+        .unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "tokio-io")]
+    #[tokio::test]
+    async fn fs_fetcher_fetch() {
+        let dir = get_test_dir("async");
+        let fetcher = FsFileFetcher::new(&dir);
+
+        assert_eq!(
+            fetcher.fetch("en-US/main.ftl").await.unwrap(),
+            "key = Value\n"
+        );
+        assert_eq!(
+            fetcher.fetch("en-US/missing.ftl").await.unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+use super::PathTemplate;
+use crate::errors::L10nRegistryError;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use unic_langid::LanguageIdentifier;
+
+/// The list of resources available in a [`FileSource`], grouped by locale.
+///
+/// Resources are identified by their path relative to the directory of the
+/// locale, so that looking up a resource doesn't require building its full
+/// path.
+///
+/// # Manifest format
+///
+/// An index can be stored in a manifest, a text file listing the resources
+/// of each locale under a `[locale]` header, one path per line:
+///
+/// ```text
+/// # Comments start with `#` and, like blank lines, are ignored.
+/// [en-US]
+/// menu.ftl
+/// panels/about.ftl
+///
+/// [pl]
+/// menu.ftl
+/// ```
+///
+/// See [`parse_manifest`](#method.parse_manifest) and
+/// [`to_manifest`](#method.to_manifest).
+///
+/// [`FileSource`]: struct.FileSource.html
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceIndex {
+    locales: FxHashMap<LanguageIdentifier, FxHashSet<String>>,
+}
+
+impl SourceIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build an index from the full paths of the resources, such as
+    /// `"browser/en-US/menu.ftl"` for the `pre_path` `"browser/{locale}/"`.
+    ///
+    /// Paths which don't belong to any of the `locales` are ignored.
+    pub fn from_full_paths<I>(
+        pre_path: &PathTemplate,
+        locales: &[LanguageIdentifier],
+        paths: I,
+    ) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut index = Self::new();
+        for path in paths {
+            let path = path.as_ref();
+            for locale in locales {
+                if let Some(res_id) = pre_path.match_path(locale, path) {
+                    index.insert(locale.clone(), res_id.to_string());
+                }
+            }
+        }
+        index
+    }
+
+    pub fn insert(&mut self, locale: LanguageIdentifier, res_id: String) {
+        self.locales.entry(locale).or_default().insert(res_id);
+    }
+
+    pub fn contains(&self, locale: &LanguageIdentifier, res_id: &str) -> bool {
+        self.locales
+            .get(locale)
+            .is_some_and(|resources| resources.contains(res_id))
+    }
+
+    pub fn locales(&self) -> impl Iterator<Item = &LanguageIdentifier> {
+        self.locales.keys()
+    }
+
+    /// Return the resources available for `locale`, in no particular order.
+    pub fn resources<'l>(&'l self, locale: &LanguageIdentifier) -> impl Iterator<Item = &'l str> {
+        self.locales
+            .get(locale)
+            .into_iter()
+            .flat_map(|resources| resources.iter().map(String::as_str))
+    }
+
+    pub fn len(&self) -> usize {
+        self.locales.values().map(FxHashSet::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Parse the manifest `source` read from `path`.
+    #[allow(clippy::result_large_err)]
+    pub fn parse_manifest(path: &str, source: &str) -> Result<Self, L10nRegistryError> {
+        let error = |line: usize, message: String| L10nRegistryError::ManifestError {
+            path: path.to_string(),
+            line: line + 1,
+            message,
+        };
+
+        let mut index = Self::new();
+        let mut locale: Option<LanguageIdentifier> = None;
+        for (line_idx, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| error(line_idx, "Unterminated locale header.".to_string()))?;
+                let langid: LanguageIdentifier = header
+                    .trim()
+                    .parse()
+                    .map_err(|err| error(line_idx, format!("Invalid locale: {}", err)))?;
+                index.locales.entry(langid.clone()).or_default();
+                locale = Some(langid);
+            } else if let Some(locale) = &locale {
+                index.insert(locale.clone(), line.to_string());
+            } else {
+                return Err(error(
+                    line_idx,
+                    "Resource listed before any locale header.".to_string(),
+                ));
+            }
+        }
+        Ok(index)
+    }
+
+    /// Serialize the index to the manifest format, with the locales and
+    /// their resources sorted.
+    pub fn to_manifest(&self) -> String {
+        let mut locales: Vec<(String, &FxHashSet<String>)> = self
+            .locales
+            .iter()
+            .map(|(locale, resources)| (locale.to_string(), resources))
+            .collect();
+        locales.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let mut result = String::new();
+        for (i, (locale, resources)) in locales.into_iter().enumerate() {
+            if i > 0 {
+                result.push('\n');
+            }
+            result.push_str(&format!("[{}]\n", locale));
+            let mut resources: Vec<&String> = resources.iter().collect();
+            resources.sort_unstable();
+            for res_id in resources {
+                result.push_str(res_id);
+                result.push('\n');
+            }
+        }
+        result
+    }
+}
+
+impl std::iter::FromIterator<(LanguageIdentifier, String)> for SourceIndex {
+    fn from_iter<I: IntoIterator<Item = (LanguageIdentifier, String)>>(iter: I) -> Self {
+        let mut index = Self::new();
+        for (locale, res_id) in iter {
+            index.insert(locale, res_id);
+        }
+        index
+    }
+}
+
+/// With the `serde` feature, an index is serialized as a map of locales to
+/// the list of their resources, both sorted.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SourceIndex {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let locales: std::collections::BTreeMap<String, Vec<&str>> = self
+            .locales
+            .iter()
+            .map(|(locale, resources)| {
+                let mut resources: Vec<&str> = resources.iter().map(String::as_str).collect();
+                resources.sort_unstable();
+                (locale.to_string(), resources)
+            })
+            .collect();
+        serde::Serialize::serialize(&locales, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SourceIndex {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let locales: FxHashMap<String, Vec<String>> =
+            serde::Deserialize::deserialize(deserializer)?;
+        let mut index = Self::new();
+        for (locale, resources) in locales {
+            let locale: LanguageIdentifier = locale
+                .parse()
+                .map_err(|err| D::Error::custom(format!("Invalid locale: {}", err)))?;
+            index.locales.entry(locale.clone()).or_default();
+            for res_id in resources {
+                index.insert(locale.clone(), res_id);
+            }
+        }
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_index_from_full_paths() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let pl: LanguageIdentifier = "pl".parse().unwrap();
+        let index = SourceIndex::from_full_paths(
+            &"browser/{locale}/".parse().unwrap(),
+            &[en_us.clone(), pl.clone()],
+            vec![
+                "browser/en-US/menu.ftl",
+                "browser/en-US/panels/about.ftl",
+                "browser/pl/menu.ftl",
+                "browser/de/menu.ftl",
+                "toolkit/en-US/menu.ftl",
+            ],
+        );
+
+        assert_eq!(index.len(), 3);
+        assert!(index.contains(&en_us, "menu.ftl"));
+        assert!(index.contains(&en_us, "panels/about.ftl"));
+        assert!(index.contains(&pl, "menu.ftl"));
+        assert!(!index.contains(&pl, "panels/about.ftl"));
+        assert!(!index.contains(&"de".parse().unwrap(), "menu.ftl"));
+
+        let mut resources: Vec<&str> = index.resources(&en_us).collect();
+        resources.sort_unstable();
+        assert_eq!(resources, vec!["menu.ftl", "panels/about.ftl"]);
+    }
+
+    #[test]
+    fn source_index_manifest() {
+        let manifest =
+            "# Generated\n[pl]\nmenu.ftl\n\n[en-US]\n  panels/about.ftl\nmenu.ftl\n[de]\n";
+        let index = SourceIndex::parse_manifest("index.txt", manifest).unwrap();
+
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        assert_eq!(index.len(), 3);
+        assert!(index.contains(&en_us, "panels/about.ftl"));
+        assert_eq!(index.locales().count(), 3);
+        assert_eq!(
+            index.to_manifest(),
+            "[de]\n\n[en-US]\nmenu.ftl\npanels/about.ftl\n\n[pl]\nmenu.ftl\n"
+        );
+        assert_eq!(
+            SourceIndex::parse_manifest("index.txt", &index.to_manifest()),
+            Ok(index)
+        );
+    }
+
+    #[test]
+    fn source_index_manifest_errors() {
+        assert_eq!(
+            SourceIndex::parse_manifest("index.txt", "menu.ftl\n"),
+            Err(L10nRegistryError::ManifestError {
+                path: "index.txt".to_string(),
+                line: 1,
+                message: "Resource listed before any locale header.".to_string(),
+            })
+        );
+        assert!(matches!(
+            SourceIndex::parse_manifest("index.txt", "[en-US]\nmenu.ftl\n[en-US\n"),
+            Err(L10nRegistryError::ManifestError { line: 3, .. })
+        ));
+        assert!(matches!(
+            SourceIndex::parse_manifest("index.txt", "[not a locale!]\n"),
+            Err(L10nRegistryError::ManifestError { line: 1, .. })
+        ));
+    }
+}
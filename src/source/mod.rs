@@ -1,40 +1,101 @@
+mod cache;
+mod description;
 mod fetcher;
+pub mod fs;
+mod index;
+mod startup_cache;
+mod template;
+pub use cache::CachePolicy;
+pub(crate) use cache::{CacheStatus, ResourceCache};
+pub use description::FileSourceDescription;
 pub use fetcher::FileFetcher;
+pub use index::SourceIndex;
+pub use startup_cache::{CachedResource, SourceSnapshot, StartupCache};
+pub use template::PathTemplate;
 
 use crate::env::ErrorReporter;
-use crate::errors::L10nRegistryError;
-use crate::fluent::FluentResource;
+use crate::errors::{
+    EntryKind, L10nRegistryError, L10nRegistrySetupError, LookupStatus, SourceLookup,
+};
+use crate::family::{
+    private::{Counter, Fetcher, Lock, Pointer},
+    Family, IntoFetcher, IntoReporter, Local, Resource,
+};
+use crate::fluent::{FluentError, FluentResource};
 
 use std::{
     borrow::Borrow,
-    cell::RefCell,
     fmt,
     hash::{Hash, Hasher},
+    io,
     pin::Pin,
     rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
     task::Poll,
+    time::Duration,
 };
 
-use futures::{future::Shared, Future, FutureExt};
+use futures::{
+    channel::oneshot,
+    future::{self, Either},
+    Future,
+};
 use rustc_hash::FxHashMap;
 use unic_langid::LanguageIdentifier;
 
 pub type RcResource = Rc<FluentResource>;
-pub type ResourceOption = Option<RcResource>;
-pub type ResourceFuture = Shared<Pin<Box<dyn Future<Output = ResourceOption>>>>;
 
-#[derive(Debug, Clone)]
-pub enum ResourceStatus {
+static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(1);
+
+/// Returns a new cache generation, greater than all the generations returned
+/// so far.
+///
+/// The generations are shared by all the sources and registries, so that
+/// the greatest generation among a set of sources changes whenever any of
+/// them is invalidated.
+pub(crate) fn next_generation() -> usize {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+pub type ResourceOption<F = Local> = Option<Resource<F>>;
+pub type ResourceFuture<F = Local> = <F as Family>::ResourceFuture;
+
+pub enum ResourceStatus<F: Family = Local> {
     /// The resource is missing.  Don't bother trying to fetch.
     Missing,
     /// The resource is loading and future will deliver the result.
-    Loading(ResourceFuture),
+    Loading(F::ResourceFuture),
     /// The resource is loaded and parsed.
-    Loaded(RcResource),
+    Loaded(Resource<F>),
+    /// The resource couldn't be fetched because of an I/O error other than
+    /// `NotFound`. It's treated as missing.
+    IoError(io::ErrorKind),
 }
 
-impl From<ResourceOption> for ResourceStatus {
-    fn from(input: ResourceOption) -> Self {
+impl<F: Family> Clone for ResourceStatus<F> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Missing => Self::Missing,
+            Self::Loading(res) => Self::Loading(res.clone()),
+            Self::Loaded(res) => Self::Loaded(res.clone()),
+            Self::IoError(kind) => Self::IoError(*kind),
+        }
+    }
+}
+
+impl<F: Family> fmt::Debug for ResourceStatus<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => f.write_str("Missing"),
+            Self::Loading(_) => f.write_str("Loading(..)"),
+            Self::Loaded(res) => f.debug_tuple("Loaded").field(&**res).finish(),
+            Self::IoError(kind) => f.debug_tuple("IoError").field(kind).finish(),
+        }
+    }
+}
+
+impl<F: Family> From<ResourceOption<F>> for ResourceStatus<F> {
+    fn from(input: ResourceOption<F>) -> Self {
         if let Some(res) = input {
             Self::Loaded(res)
         } else {
@@ -43,8 +104,31 @@ impl From<ResourceOption> for ResourceStatus {
     }
 }
 
-impl Future for ResourceStatus {
-    type Output = ResourceOption;
+impl<F: Family> ResourceStatus<F> {
+    /// The resource if it's loaded.
+    fn loaded(&self) -> ResourceOption<F> {
+        match self {
+            Self::Loaded(res) => Some(res.clone()),
+            Self::Missing | Self::Loading(_) | Self::IoError(_) => None,
+        }
+    }
+}
+
+impl<F: Family> CacheStatus for ResourceStatus<F> {
+    fn loaded_size(&self) -> Option<usize> {
+        match self {
+            Self::Loaded(res) => Some(res.source().len()),
+            Self::Missing | Self::Loading(_) | Self::IoError(_) => None,
+        }
+    }
+
+    fn is_missing(&self) -> bool {
+        matches!(self, Self::Missing | Self::IoError(_))
+    }
+}
+
+impl<F: Family> Future for ResourceStatus<F> {
+    type Output = ResourceOption<F>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
         use ResourceStatus::*;
@@ -52,7 +136,7 @@ impl Future for ResourceStatus {
         let this = &mut *self;
 
         match this {
-            Missing => None.into(),
+            Missing | IoError(_) => None.into(),
             Loaded(res) => Some(res.clone()).into(),
             Loading(res) => Pin::new(res).poll(cx),
         }
@@ -62,111 +146,239 @@ impl Future for ResourceStatus {
 /// `FileSource` provides a generic fetching and caching of fluent resources.
 /// The user of `FileSource` provides a [`FileFetcher`](trait.FileFetcher.html)
 /// implementation and `FileSource` takes care of the rest.
-#[derive(Clone)]
-pub struct FileSource {
+///
+/// The source is built on the pointer and lock types of the [`Family`] `F`.
+///
+/// [`Family`]: ../family/trait.Family.html
+pub struct FileSource<F: Family = Local> {
     pub name: String,
-    pub pre_path: String,
+    pub pre_path: PathTemplate,
     locales: Vec<LanguageIdentifier>,
-    shared: Rc<Inner>,
-    index: Option<Vec<String>>,
+    shared: F::Ptr<Inner<F>>,
+    index: Option<SourceIndex>,
     pub options: FileSourceOptions,
 }
 
-struct Inner {
-    fetcher: Box<dyn FileFetcher>,
-    error_reporter: Option<RefCell<Box<dyn ErrorReporter>>>,
-    entries: RefCell<FxHashMap<String, ResourceStatus>>,
+type PendingLoads<F> = FxHashMap<String, (usize, oneshot::Sender<ResourceOption<F>>)>;
+
+struct Inner<F: Family> {
+    fetcher: Box<F::Fetcher>,
+    error_reporter: Option<Box<F::Reporter>>,
+    entries: F::Lock<ResourceCache<ResourceStatus<F>>>,
+    /// Senders used to resolve in-flight async loads with the result of
+    /// a sync load of the same resource, along with the generation in which
+    /// the load has been started.
+    pending: F::Lock<PendingLoads<F>>,
+    /// Set to a new generation every time the cache is invalidated.
+    generation: F::Counter,
+}
+
+impl<F: Family> Clone for FileSource<F> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            pre_path: self.pre_path.clone(),
+            locales: self.locales.clone(),
+            shared: self.shared.clone(),
+            index: self.index.clone(),
+            options: self.options.clone(),
+        }
+    }
 }
 
-impl fmt::Display for FileSource {
+impl<F: Family> fmt::Display for FileSource<F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.name)
     }
 }
 
-impl PartialEq<FileSource> for FileSource {
+impl<F: Family> PartialEq<FileSource<F>> for FileSource<F> {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
     }
 }
 
-impl Eq for FileSource {}
+impl<F: Family> Eq for FileSource<F> {}
 
-impl Hash for FileSource {
+impl<F: Family> Hash for FileSource<F> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.name.hash(state)
     }
 }
 
-#[derive(PartialEq, Clone, Debug)]
+/// The options of a [`FileSource`].
+///
+/// [`FileSource`]: struct.FileSource.html
+#[derive(PartialEq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct FileSourceOptions {
     pub allow_override: bool,
+    /// The number of times a fetch is retried when the fetcher fails with a
+    /// transient I/O error, i.e. `Interrupted`, `WouldBlock` or `TimedOut`.
+    /// Other errors are reported without retrying.
+    pub io_error_retries: usize,
+    /// The priority of the source in a registry. Sources with a higher
+    /// priority are tried first. Among sources with the same priority, the
+    /// last registered one is tried first.
+    ///
+    /// The priority is read when the source is registered or updated.
+    pub priority: i32,
+    /// The limits of the cache of resources. The policy is applied when the
+    /// `FileSource` is created and changing it afterwards has no effect.
+    pub cache: CachePolicy,
 }
 
-impl Default for FileSourceOptions {
-    fn default() -> Self {
-        Self {
-            allow_override: false,
-        }
-    }
-}
-
-impl FileSource {
+impl<F: Family> FileSource<F> {
     /// Create a `FileSource` using the provided [`FileFetcher`](../trait.FileFetcher.html).
     pub fn new(
         name: String,
         locales: Vec<LanguageIdentifier>,
-        pre_path: String,
+        pre_path: PathTemplate,
         options: FileSourceOptions,
-        fetcher: impl FileFetcher + 'static,
+        fetcher: impl IntoFetcher<F>,
     ) -> Self {
-        FileSource {
+        Self::with_fetcher(
             name,
-            pre_path,
             locales,
-            index: None,
-            shared: Rc::new(Inner {
-                entries: RefCell::new(FxHashMap::default()),
-                fetcher: Box::new(fetcher),
-                error_reporter: None,
-            }),
+            pre_path,
             options,
-        }
+            fetcher.into_fetcher(),
+            None,
+        )
     }
 
-    pub fn new_with_index(
+    /// Create a `FileSource` from the path template `pre_path`, such as
+    /// `"browser/{locale}/"`, as [`new`](#method.new) did before it took a
+    /// parsed [`PathTemplate`](struct.PathTemplate.html).
+    ///
+    /// Returns an error if the template is invalid.
+    pub fn try_new(
         name: String,
         locales: Vec<LanguageIdentifier>,
         pre_path: String,
         options: FileSourceOptions,
-        fetcher: impl FileFetcher + 'static,
+        fetcher: impl IntoFetcher<F>,
+    ) -> Result<Self, L10nRegistrySetupError> {
+        let pre_path = PathTemplate::parse(&pre_path)?;
+        Ok(Self::new(name, locales, pre_path, options, fetcher))
+    }
+
+    pub fn new_with_index(
+        name: String,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: PathTemplate,
+        options: FileSourceOptions,
+        fetcher: impl IntoFetcher<F>,
         index: Vec<String>,
+    ) -> Self {
+        let index = SourceIndex::from_full_paths(&pre_path, &locales, index);
+        Self::with_fetcher(
+            name,
+            locales,
+            pre_path,
+            options,
+            fetcher.into_fetcher(),
+            Some(index),
+        )
+    }
+
+    pub fn new_with_source_index(
+        name: String,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: PathTemplate,
+        options: FileSourceOptions,
+        fetcher: impl IntoFetcher<F>,
+        index: SourceIndex,
+    ) -> Self {
+        Self::with_fetcher(
+            name,
+            locales,
+            pre_path,
+            options,
+            fetcher.into_fetcher(),
+            Some(index),
+        )
+    }
+
+    /// Create a `FileSource` whose index is read from the manifest at
+    /// `manifest_path` using `fetcher`.
+    ///
+    /// See [`SourceIndex`](struct.SourceIndex.html) for the format of the
+    /// manifest.
+    #[allow(clippy::result_large_err)]
+    pub fn new_with_manifest(
+        name: String,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: PathTemplate,
+        options: FileSourceOptions,
+        fetcher: impl IntoFetcher<F>,
+        manifest_path: &str,
+    ) -> Result<Self, L10nRegistryError> {
+        let fetcher = fetcher.into_fetcher();
+        let manifest =
+            fetcher
+                .fetch_sync(manifest_path)
+                .map_err(|err| L10nRegistryError::IoError {
+                    path: manifest_path.to_string(),
+                    kind: err.kind(),
+                    message: err.to_string(),
+                })?;
+        let index = SourceIndex::parse_manifest(manifest_path, &manifest)?;
+        Ok(Self::with_fetcher(
+            name,
+            locales,
+            pre_path,
+            options,
+            fetcher,
+            Some(index),
+        ))
+    }
+
+    fn with_fetcher(
+        name: String,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: PathTemplate,
+        options: FileSourceOptions,
+        fetcher: Box<F::Fetcher>,
+        index: Option<SourceIndex>,
     ) -> Self {
         FileSource {
             name,
             pre_path,
             locales,
-            index: Some(index),
-            shared: Rc::new(Inner {
-                entries: RefCell::new(FxHashMap::default()),
-                fetcher: Box::new(fetcher),
-                error_reporter: None,
-            }),
+            index,
+            shared: Pointer::new(Inner::new(fetcher, options.cache.clone())),
             options,
         }
     }
 
-    pub fn set_reporter(&mut self, reporter: impl ErrorReporter + 'static) {
-        let mut shared = Rc::get_mut(&mut self.shared).unwrap();
-        shared.error_reporter = Some(RefCell::new(Box::new(reporter)));
+    /// Report the errors encountered while loading resources to `reporter`.
+    ///
+    /// The reporter has to be set before the source is cloned, for example
+    /// by registering it, and `L10nRegistrySetupError::SourceShared` is
+    /// returned otherwise.
+    pub fn set_reporter(
+        &mut self,
+        reporter: impl IntoReporter<F>,
+    ) -> Result<(), L10nRegistrySetupError> {
+        match Pointer::get_mut(&mut self.shared) {
+            Some(shared) => {
+                shared.error_reporter = Some(reporter.into_reporter());
+                Ok(())
+            }
+            None => Err(L10nRegistrySetupError::SourceShared {
+                name: self.name.clone(),
+            }),
+        }
     }
 }
 
-fn calculate_pos_in_source(source: &str, idx: usize) -> (usize, usize) {
+pub(crate) fn calculate_pos_in_source(source: &str, idx: usize) -> (usize, usize) {
     let mut ptr = 0;
     let mut result = (1, 1);
     for line in source.lines() {
-        let bytes = line.as_bytes().len();
+        let bytes = line.len();
         if ptr + bytes < idx {
             ptr += bytes + 1;
             result.0 += 1;
@@ -178,83 +390,145 @@ fn calculate_pos_in_source(source: &str, idx: usize) -> (usize, usize) {
     result
 }
 
-impl FileSource {
-    fn get_path(&self, locale: &LanguageIdentifier, path: &str) -> String {
-        format!(
-            "{}{}",
-            self.pre_path.replace("{locale}", &locale.to_string()),
-            path
-        )
+/// Return the byte offset of the line defining the message `id`, or the term
+/// `-id` if `is_term` is set, in the source of `res`.
+///
+/// Entries start at the beginning of a line with their id followed by `=`,
+/// while the lines of their values and comments can't.
+fn entry_offset(res: &FluentResource, id: &str, is_term: bool) -> Option<usize> {
+    let prefix = if is_term { "-" } else { "" };
+    let mut offset = 0;
+    for line in res.source().split('\n') {
+        let rest = line
+            .strip_prefix(prefix)
+            .and_then(|line| line.strip_prefix(id));
+        if rest.is_some_and(|rest| rest.trim_start().starts_with('=')) {
+            return Some(offset);
+        }
+        offset += line.len() + 1;
     }
+    None
+}
 
-    fn fetch_sync(&self, full_path: &str) -> ResourceOption {
-        self.shared
-            .fetcher
-            .fetch_sync(full_path)
-            .ok()
-            .map(|source| match FluentResource::try_new(source) {
-                Ok(res) => Rc::new(res),
-                Err((res, errors)) => {
-                    if let Some(reporter) = &self.shared.error_reporter {
-                        reporter.borrow().report_errors(
-                            errors
-                                .into_iter()
-                                .map(|e| L10nRegistryError::FluentError {
-                                    path: full_path.to_string(),
-                                    loc: Some(calculate_pos_in_source(res.source(), e.pos.start)),
-                                    error: e.into(),
-                                })
-                                .collect(),
-                        );
-                    }
-                    Rc::new(res)
+/// Convert the `errors` of adding `res`, loaded from `path` in the source
+/// `source_name`, to a bundle which already contains the resources `added`,
+/// keyed by their full paths.
+pub(crate) fn add_resource_errors<R: AsRef<FluentResource>>(
+    source_name: &str,
+    path: &str,
+    res: &FluentResource,
+    added: &[(String, R)],
+    errors: Vec<FluentError>,
+) -> Vec<L10nRegistryError> {
+    errors
+        .into_iter()
+        .map(|error| match &error {
+            FluentError::Overriding { kind, id } => {
+                let kind = EntryKind::from_fluent(kind);
+                let is_term = kind == EntryKind::Term;
+                L10nRegistryError::OverridingEntry {
+                    source: source_name.to_string(),
+                    path: path.to_string(),
+                    loc: entry_offset(res, id, is_term)
+                        .map(|offset| calculate_pos_in_source(res.source(), offset)),
+                    original_path: added
+                        .iter()
+                        .find(|(_, added)| entry_offset(added.as_ref(), id, is_term).is_some())
+                        .map(|(path, _)| path.clone()),
+                    kind,
+                    id: id.clone(),
                 }
-            })
+            }
+            _ => L10nRegistryError::FluentError {
+                path: path.to_string(),
+                loc: None,
+                error,
+            },
+        })
+        .collect()
+}
+
+/// Parse the `source` of the resource at `path`, returning the resource along
+/// with the errors encountered while parsing it.
+pub(crate) fn parse_resource(
+    path: &str,
+    source: String,
+) -> (FluentResource, Vec<L10nRegistryError>) {
+    match FluentResource::try_new(source) {
+        Ok(res) => (res, vec![]),
+        Err((res, errors)) => {
+            let errors = errors
+                .into_iter()
+                .map(|e| L10nRegistryError::FluentError {
+                    path: path.to_string(),
+                    loc: Some(calculate_pos_in_source(res.source(), e.pos.start)),
+                    error: e.into(),
+                })
+                .collect();
+            (res, errors)
+        }
+    }
+}
+
+impl<F: Family> FileSource<F> {
+    pub(crate) fn get_path(&self, locale: &LanguageIdentifier, path: &str) -> String {
+        self.pre_path.resolve(locale, path)
+    }
+
+    fn fetch_sync(&self, full_path: &str) -> ResourceStatus<F> {
+        let mut retries = self.options.io_error_retries;
+        loop {
+            match self.shared.fetcher.fetch_sync(full_path) {
+                Ok(source) => {
+                    return ResourceStatus::Loaded(self.shared.parse_resource(full_path, source))
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    return ResourceStatus::Missing
+                }
+                Err(err) if retries == 0 || !is_retryable(err.kind()) => {
+                    let kind = err.kind();
+                    self.shared.report_io_error(full_path, err);
+                    return ResourceStatus::IoError(kind);
+                }
+                Err(_) => retries -= 1,
+            }
+        }
     }
 
     /// Attempt to synchronously fetch resource for the combination of `locale`
     /// and `path`. Returns `Some(ResourceResult)` if the resource is available,
     /// else `None`.
+    ///
+    /// If the resource is being loaded asynchronously, it is loaded
+    /// synchronously and all pending futures resolve with the same resource.
+    ///
+    /// The cache is not locked while the resource is being fetched. If the
+    /// resource is resolved concurrently by another load, the cached resource
+    /// is returned.
+    ///
+    /// The `overload` argument is deprecated and ignored: a pending async load
+    /// is always overloaded now.
     pub fn fetch_file_sync(
         &self,
         locale: &LanguageIdentifier,
         path: &str,
-        overload: bool,
-    ) -> ResourceOption {
+        _overload: bool,
+    ) -> ResourceOption<F> {
         use ResourceStatus::*;
 
         if self.has_file(locale, path) == Some(false) {
             return None;
         }
 
-        let full_path = self.get_path(locale, &path);
-
-        let res = self
-            .shared
-            .lookup_resource(full_path.clone(), || self.fetch_sync(&full_path).into());
-
-        match res {
-            Missing => None,
-            Loaded(res) => Some(res),
-            Loading(..) if overload => {
-                // A sync load has been requested for the same resource that has
-                // a pending async load in progress. How do we handle this?
-                //
-                // Ideally, we would sync load and resolve all the pending
-                // futures with the result. With the current Futures and
-                // combinators, it's unclear how to proceed. One potential
-                // solution is to store a oneshot::Sender and
-                // Shared<oneshot::Receiver>. When the async loading future
-                // resolves it would check that the state is still `Loading`,
-                // and if so, send the result. The sync load would do the same
-                // send on the oneshot::Sender.
-                //
-                // For now, we warn and return the resource, paying the cost of
-                // duplication of the resource.
-                self.fetch_sync(&full_path)
-            }
-            Loading(..) => {
-                panic!("[l10nregistry] Attempting to synchronously load file {} while it's being loaded asynchronously.", &full_path);
+        let full_path = self.get_path(locale, path);
+
+        let status = self.shared.entries.write().get(&full_path);
+        match status {
+            Some(Missing) | Some(IoError(_)) => None,
+            Some(Loaded(res)) => Some(res),
+            Some(Loading(..)) | None => {
+                let resource = self.fetch_sync(&full_path);
+                self.shared.update_resource(full_path, resource)
             }
         }
     }
@@ -262,19 +536,78 @@ impl FileSource {
     /// Attempt to fetch resource for the combination of `locale` and `path`.
     /// Returns [`ResourceStatus`](enum.ResourceStatus.html) which is
     /// a `Future` that can be polled.
-    pub fn fetch_file(&self, locale: &LanguageIdentifier, path: &str) -> ResourceStatus {
-        use ResourceStatus::*;
-
+    pub fn fetch_file(&self, locale: &LanguageIdentifier, path: &str) -> ResourceStatus<F> {
         if self.has_file(locale, path) == Some(false) {
             return ResourceStatus::Missing;
         }
 
         let full_path = self.get_path(locale, path);
 
-        self.shared.lookup_resource(full_path.clone(), || {
-            let shared = self.shared.clone();
-            Loading(read_resource(full_path, shared).boxed_local().shared())
-        })
+        let mut entries = self.shared.entries.write();
+        if let Some(status) = entries.get(&full_path) {
+            return status;
+        }
+        let (sender, receiver) = oneshot::channel();
+        let load_id = self.shared.generation.get();
+        self.shared
+            .pending
+            .write()
+            .insert(full_path.clone(), (load_id, sender));
+        let status =
+            ResourceStatus::Loading(F::load_resource(self, full_path.clone(), load_id, receiver));
+        entries.insert(full_path, status.clone());
+        status
+    }
+
+    /// Load the resource at `full_path` asynchronously, unless the load
+    /// `load_id` is resolved first through `sync_result`.
+    pub(crate) fn load_resource(
+        &self,
+        full_path: String,
+        load_id: usize,
+        sync_result: oneshot::Receiver<ResourceOption<F>>,
+    ) -> impl Future<Output = ResourceOption<F>> {
+        resolve_resource::<F>(
+            full_path,
+            self.shared.clone(),
+            self.options.io_error_retries,
+            load_id,
+            sync_result,
+        )
+    }
+
+    /// Remove the cached resource for the combination of `locale` and `path`,
+    /// so that it is fetched again on the next request.
+    ///
+    /// Pending async loads of the resource still resolve, but their result is
+    /// not stored in the cache.
+    pub fn invalidate(&self, locale: &LanguageIdentifier, path: &str) {
+        let full_path = self.get_path(locale, path);
+        self.shared.invalidate(|p| p == full_path);
+    }
+
+    /// Remove all cached resources for `locale`.
+    pub fn invalidate_locale(&self, locale: &LanguageIdentifier) {
+        self.shared
+            .invalidate(|p| self.pre_path.match_path(locale, p).is_some());
+    }
+
+    /// Remove all cached resources.
+    pub fn clear_cache(&self) {
+        self.shared.invalidate(|_| true);
+    }
+
+    /// Returns the generation of the cache of the source, which is replaced
+    /// by a greater one every time the cache is invalidated or restored from
+    /// a snapshot.
+    pub(crate) fn cache_generation(&self) -> usize {
+        self.shared.generation.get()
+    }
+
+    /// How long a resource is remembered as missing by the cache, if it's
+    /// limited.
+    pub(crate) fn missing_ttl(&self) -> Option<Duration> {
+        self.shared.entries.read().policy().missing_ttl
     }
 
     /// Determine if the `FileSource` has a loaded resource for the combination
@@ -286,11 +619,10 @@ impl FileSource {
         if !self.locales.contains(locale) {
             Some(false)
         } else {
-            let full_path = self.get_path(locale, path);
             if let Some(index) = &self.index {
-                return Some(index.iter().any(|p| p == &full_path));
+                return Some(index.contains(locale, path));
             }
-            self.shared.has_file(&full_path)
+            self.shared.has_file(&self.get_path(locale, path))
         }
     }
 
@@ -298,12 +630,112 @@ impl FileSource {
         &self.locales
     }
 
-    pub fn get_index(&self) -> Option<&Vec<String>> {
+    /// Describe how the resource `path` for `locale` is resolved by the
+    /// source, without fetching it.
+    pub fn lookup(&self, locale: &LanguageIdentifier, path: &str) -> SourceLookup {
+        let full_path = self.get_path(locale, path);
+        let status = if !self.locales.contains(locale) {
+            LookupStatus::LocaleNotDeclared
+        } else if self
+            .index
+            .as_ref()
+            .is_some_and(|index| !index.contains(locale, path))
+        {
+            LookupStatus::NotInIndex
+        } else {
+            match self.shared.entries.read().peek(&full_path) {
+                Some(ResourceStatus::Loaded(_)) => LookupStatus::Available,
+                Some(ResourceStatus::Missing) => LookupStatus::NotFound,
+                Some(ResourceStatus::IoError(kind)) => LookupStatus::IoError { kind: *kind },
+                Some(ResourceStatus::Loading(_)) | None => LookupStatus::Unknown,
+            }
+        };
+        SourceLookup {
+            source: self.name.clone(),
+            template: self.pre_path.to_string(),
+            path: full_path,
+            status,
+        }
+    }
+
+    /// A hash of the configuration of the source, which changes whenever the
+    /// source may provide different resources.
+    pub fn identity(&self) -> u64 {
+        let mut key = format!("{}\n{}\n", self.name, self.pre_path);
+        for locale in &self.locales {
+            key.push_str(&locale.to_string());
+            key.push('\n');
+        }
+        startup_cache::content_hash(key.as_bytes())
+    }
+
+    /// Take a snapshot of the cached resources, leaving out the resources
+    /// which are being loaded.
+    pub fn snapshot(&self) -> SourceSnapshot {
+        let entries = self.shared.entries.read();
+        let mut resources: Vec<(String, CachedResource)> = entries
+            .iter()
+            .filter_map(|(path, status)| {
+                let resource = match status {
+                    ResourceStatus::Missing => CachedResource::Missing,
+                    ResourceStatus::Loaded(res) => CachedResource::Loaded {
+                        hash: startup_cache::content_hash(res.source().as_bytes()),
+                        source: res.source().to_string(),
+                    },
+                    // I/O errors may be transient, they aren't persisted.
+                    ResourceStatus::Loading(_) | ResourceStatus::IoError(_) => return None,
+                };
+                Some((path.to_string(), resource))
+            })
+            .collect();
+        resources.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        SourceSnapshot {
+            name: self.name.clone(),
+            identity: self.identity(),
+            resources,
+        }
+    }
+
+    /// Pre-populate the cache with the resources of `snapshot`, returning the
+    /// number of restored resources.
+    ///
+    /// Nothing is restored if the snapshot has been taken by a source with
+    /// a different [`identity`](#method.identity). Resources which are
+    /// already cached and resources whose content doesn't match their hash
+    /// are skipped.
+    pub fn restore_snapshot(&self, snapshot: &SourceSnapshot) -> usize {
+        if snapshot.name != self.name || snapshot.identity != self.identity() {
+            return 0;
+        }
+        let mut restored = 0;
+        for (path, resource) in &snapshot.resources {
+            if self.shared.entries.read().peek(path).is_some() {
+                continue;
+            }
+            let status = match resource {
+                CachedResource::Missing => ResourceStatus::Missing,
+                CachedResource::Loaded { hash, source } => {
+                    if startup_cache::content_hash(source.as_bytes()) != *hash {
+                        continue;
+                    }
+                    ResourceStatus::Loaded(self.shared.parse_resource(path, source.clone()))
+                }
+            };
+            self.shared.entries.write().insert(path.clone(), status);
+            restored += 1;
+        }
+        if restored > 0 {
+            self.shared.generation.set(next_generation());
+        }
+        restored
+    }
+
+    pub fn get_index(&self) -> Option<&SourceIndex> {
         self.index.as_ref()
     }
 }
 
-impl std::fmt::Debug for FileSource {
+impl<F: Family> std::fmt::Debug for FileSource<F> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
         if let Some(index) = &self.index {
             f.debug_struct("FileSource")
@@ -322,63 +754,343 @@ impl std::fmt::Debug for FileSource {
     }
 }
 
-impl Inner {
-    fn lookup_resource<F>(&self, path: String, f: F) -> ResourceStatus
+impl<F: Family> Inner<F> {
+    fn new(fetcher: Box<F::Fetcher>, policy: CachePolicy) -> Self {
+        Self {
+            fetcher,
+            error_reporter: None,
+            entries: Lock::new(ResourceCache::new(policy)),
+            pending: Lock::new(FxHashMap::default()),
+            generation: Default::default(),
+        }
+    }
+
+    /// Store the result of a load of a resource and resolve the pending
+    /// future of the resource with it.
+    ///
+    /// If the resource has already been resolved by a competing load, the
+    /// `status` is discarded and the cached resource is used instead.
+    fn update_resource(&self, path: String, status: ResourceStatus<F>) -> ResourceOption<F> {
+        let resource = {
+            let mut entries = self.entries.write();
+            match entries.peek(&path) {
+                Some(ResourceStatus::Loading(_)) | None => {
+                    let resource = status.loaded();
+                    entries.insert(path.clone(), status);
+                    resource
+                }
+                Some(cached) => cached.loaded(),
+            }
+        };
+        if let Some((_, sender)) = self.pending.write().remove(&path) {
+            // The receiver is gone if the pending future has been
+            // dropped, in which case there is nobody to notify.
+            let _ = sender.send(resource.clone());
+        }
+        resource
+    }
+
+    /// Store the result of the async load `load_id` of a resource.
+    ///
+    /// If the load has been superseded, either by a sync load of the same
+    /// resource or by an invalidation of the cache, the cache is left
+    /// untouched. If the resource has already been resolved by a competing
+    /// load, the `status` is discarded and the cached resource is returned
+    /// instead.
+    fn complete_load(
+        &self,
+        path: String,
+        load_id: usize,
+        status: ResourceStatus<F>,
+    ) -> ResourceOption<F> {
+        let is_current = matches!(
+            self.pending.read().get(&path),
+            Some((id, _)) if *id == load_id
+        );
+        if is_current {
+            return self.update_resource(path, status);
+        }
+        match self.entries.read().peek(&path) {
+            Some(ResourceStatus::Loading(_)) | None => status.loaded(),
+            Some(cached) => cached.loaded(),
+        }
+    }
+
+    fn invalidate<P>(&self, f: P)
     where
-        F: FnOnce() -> ResourceStatus,
+        P: Fn(&str) -> bool,
     {
-        let mut lock = self.entries.borrow_mut();
-        lock.entry(path).or_insert_with(|| f()).clone()
+        self.entries.write().retain(|path| !f(path));
+        // Dropping the senders lets the pending loads resolve with the
+        // result of their own fetch.
+        self.pending.write().retain(|path, _| !f(path));
+        self.generation.set(next_generation());
     }
 
-    fn update_resource(&self, path: String, resource: ResourceOption) -> ResourceOption {
-        let mut lock = self.entries.borrow_mut();
-        let entry = lock.get_mut(&path);
-        match entry {
-            Some(entry) => *entry = resource.clone().into(),
-            _ => panic!("Expected "),
+    fn parse_resource(&self, path: &str, source: String) -> Resource<F> {
+        let (res, errors) = parse_resource(path, source);
+        if !errors.is_empty() {
+            self.report_errors(errors);
+        }
+        Pointer::new(res)
+    }
+
+    fn report_io_error(&self, path: &str, error: io::Error) {
+        self.report_errors(vec![L10nRegistryError::IoError {
+            path: path.to_string(),
+            kind: error.kind(),
+            message: error.to_string(),
+        }]);
+    }
+
+    fn report_errors(&self, errors: Vec<L10nRegistryError>) {
+        if let Some(reporter) = &self.error_reporter {
+            reporter.report_errors(errors);
         }
-        resource
     }
 
     pub fn has_file(&self, full_path: &str) -> Option<bool> {
-        match self.entries.borrow().get(full_path) {
-            Some(ResourceStatus::Missing) => Some(false),
+        match self.entries.read().peek(full_path) {
+            Some(ResourceStatus::Missing) | Some(ResourceStatus::IoError(_)) => Some(false),
             Some(ResourceStatus::Loaded(_)) => Some(true),
             Some(ResourceStatus::Loading(_)) | None => None,
         }
     }
 }
 
-async fn read_resource(path: String, shared: Rc<Inner>) -> ResourceOption {
-    let resource =
-        shared.fetcher.fetch(&path).await.ok().map(|source| {
-            match FluentResource::try_new(source) {
-                Ok(res) => Rc::new(res),
-                Err((res, errors)) => {
-                    if let Some(reporter) = &shared.error_reporter.borrow() {
-                        reporter.borrow().report_errors(
-                            errors
-                                .into_iter()
-                                .map(|e| L10nRegistryError::FluentError {
-                                    path: path.clone(),
-                                    loc: Some(calculate_pos_in_source(res.source(), e.pos.start)),
-                                    error: e.into(),
-                                })
-                                .collect(),
-                        );
-                    }
-                    Rc::new(res)
-                }
+/// Whether a fetch failing with an I/O error of `kind` may succeed if it is
+/// attempted again.
+pub(crate) fn is_retryable(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Resolve an async load of a resource with either the result of the fetch,
+/// or the result of a sync load of the same resource, whichever comes first.
+async fn resolve_resource<F: Family>(
+    path: String,
+    shared: F::Ptr<Inner<F>>,
+    retries: usize,
+    load_id: usize,
+    sync_result: oneshot::Receiver<ResourceOption<F>>,
+) -> ResourceOption<F> {
+    let fetch = Box::pin(read_resource::<F>(path, shared, retries, load_id));
+    match future::select(fetch, sync_result).await {
+        Either::Left((resource, _)) => resource,
+        Either::Right((Ok(resource), _)) => resource,
+        Either::Right((Err(_), fetch)) => fetch.await,
+    }
+}
+
+async fn read_resource<F: Family>(
+    path: String,
+    shared: F::Ptr<Inner<F>>,
+    mut retries: usize,
+    load_id: usize,
+) -> ResourceOption<F> {
+    let status = loop {
+        match shared.fetcher.fetch(&path).await {
+            Ok(source) => break ResourceStatus::Loaded(shared.parse_resource(&path, source)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => break ResourceStatus::Missing,
+            Err(err) if retries == 0 || !is_retryable(err.kind()) => {
+                let kind = err.kind();
+                shared.report_io_error(&path, err);
+                break ResourceStatus::IoError(kind);
             }
-        });
+            Err(_) => retries -= 1,
+        }
+    };
     // insert the resource into the cache
-    shared.update_resource(path, resource)
+    shared.complete_load(path, load_id, status)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use async_trait::async_trait;
+    use std::cell::{Cell, RefCell};
+
+    struct FailingFileFetcher {
+        kind: io::ErrorKind,
+        attempts: Rc<Cell<usize>>,
+    }
+
+    #[async_trait(?Send)]
+    impl FileFetcher for FailingFileFetcher {
+        fn fetch_sync(&self, _path: &str) -> io::Result<String> {
+            self.attempts.set(self.attempts.get() + 1);
+            Err(io::Error::new(self.kind, "Failed to read."))
+        }
+
+        async fn fetch(&self, path: &str) -> io::Result<String> {
+            self.fetch_sync(path)
+        }
+    }
+
+    struct ConstFileFetcher;
+
+    #[async_trait(?Send)]
+    impl FileFetcher for ConstFileFetcher {
+        fn fetch_sync(&self, _path: &str) -> io::Result<String> {
+            Ok("key = Value\n".to_string())
+        }
+
+        async fn fetch(&self, path: &str) -> io::Result<String> {
+            self.fetch_sync(path)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct ErrorCollector(Rc<RefCell<Vec<L10nRegistryError>>>);
+
+    impl ErrorCollector {
+        fn errors(&self) -> Vec<L10nRegistryError> {
+            RefCell::borrow(&self.0).clone()
+        }
+    }
+
+    impl ErrorReporter for ErrorCollector {
+        fn report_errors(&self, errors: Vec<L10nRegistryError>) {
+            self.0.borrow_mut().extend(errors);
+        }
+    }
+
+    fn get_failing_source(
+        kind: io::ErrorKind,
+        io_error_retries: usize,
+    ) -> (FileSource, Rc<Cell<usize>>, ErrorCollector) {
+        let attempts = Rc::new(Cell::new(0));
+        let reporter = ErrorCollector::default();
+        let mut source = FileSource::new(
+            "failing".to_string(),
+            vec!["en-US".parse().unwrap()],
+            "{locale}/".parse().unwrap(),
+            FileSourceOptions {
+                io_error_retries,
+                ..Default::default()
+            },
+            FailingFileFetcher {
+                kind,
+                attempts: attempts.clone(),
+            },
+        );
+        source.set_reporter(reporter.clone()).unwrap();
+        (source, attempts, reporter)
+    }
+
+    #[test]
+    fn file_source_not_found_is_missing() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let (source, attempts, reporter) = get_failing_source(io::ErrorKind::NotFound, 2);
+
+        assert!(source.fetch_file_sync(&en_us, "main.ftl", false).is_none());
+        assert_eq!(source.has_file(&en_us, "main.ftl"), Some(false));
+        assert_eq!(
+            source.lookup(&en_us, "main.ftl").status,
+            LookupStatus::NotFound
+        );
+        assert_eq!(attempts.get(), 1);
+        assert!(reporter.errors().is_empty());
+    }
+
+    #[test]
+    fn file_source_io_error_sync() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let (source, attempts, reporter) = get_failing_source(io::ErrorKind::TimedOut, 2);
+
+        assert!(source.fetch_file_sync(&en_us, "main.ftl", false).is_none());
+        assert_eq!(attempts.get(), 3);
+        assert_eq!(
+            reporter.errors(),
+            vec![L10nRegistryError::IoError {
+                path: "en-US/main.ftl".to_string(),
+                kind: io::ErrorKind::TimedOut,
+                message: "Failed to read.".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn file_source_io_error_not_retryable() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let (source, attempts, reporter) = get_failing_source(io::ErrorKind::PermissionDenied, 2);
+
+        assert!(source.fetch_file_sync(&en_us, "main.ftl", false).is_none());
+        assert_eq!(source.has_file(&en_us, "main.ftl"), Some(false));
+        assert_eq!(
+            source.lookup(&en_us, "main.ftl").status,
+            LookupStatus::IoError {
+                kind: io::ErrorKind::PermissionDenied
+            }
+        );
+        assert_eq!(attempts.get(), 1);
+        assert_eq!(
+            reporter.errors(),
+            vec![L10nRegistryError::IoError {
+                path: "en-US/main.ftl".to_string(),
+                kind: io::ErrorKind::PermissionDenied,
+                message: "Failed to read.".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn file_source_io_error_async() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let (source, attempts, reporter) = get_failing_source(io::ErrorKind::Interrupted, 1);
+
+        let file = futures::executor::block_on(source.fetch_file(&en_us, "main.ftl"));
+        assert!(file.is_none());
+        assert_eq!(attempts.get(), 2);
+        assert_eq!(
+            source.lookup(&en_us, "main.ftl").status,
+            LookupStatus::IoError {
+                kind: io::ErrorKind::Interrupted
+            }
+        );
+        assert_eq!(
+            reporter.errors(),
+            vec![L10nRegistryError::IoError {
+                path: "en-US/main.ftl".to_string(),
+                kind: io::ErrorKind::Interrupted,
+                message: "Failed to read.".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn file_source_invalidate_locale() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let de: LanguageIdentifier = "de".parse().unwrap();
+
+        for template in &["{locale}/{res_id}.ftl", "browser-{locale}"] {
+            let source: FileSource = FileSource::try_new(
+                "test".to_string(),
+                vec![en_us.clone(), de.clone()],
+                template.to_string(),
+                Default::default(),
+                ConstFileFetcher,
+            )
+            .unwrap();
+            assert!(source.fetch_file_sync(&en_us, "main", false).is_some());
+            assert!(source.fetch_file_sync(&de, "main", false).is_some());
+
+            source.invalidate_locale(&en_us);
+            assert_eq!(source.has_file(&en_us, "main"), None);
+            assert_eq!(source.has_file(&de, "main"), Some(true));
+        }
+
+        let source: Result<FileSource, _> = FileSource::try_new(
+            "test".to_string(),
+            vec![en_us],
+            "{lang}/".to_string(),
+            Default::default(),
+            ConstFileFetcher,
+        );
+        assert!(source.is_err());
+    }
 
     #[test]
     fn calculate_source_pos() {
@@ -400,6 +1112,60 @@ key2 = Value 2
         let result = calculate_pos_in_source(source, 13);
         assert_eq!(result, (3, 1));
     }
+
+    #[test]
+    fn add_resource_errors_overriding() {
+        let original = Rc::new(
+            FluentResource::try_new("key1 = Value\n-brand = Nightly\n".to_string()).unwrap(),
+        );
+        let res = Rc::new(
+            FluentResource::try_new("key2 = Value\n\n-brand = Firefox\nkey1 = Other\n".to_string())
+                .unwrap(),
+        );
+
+        let mut bundle = crate::fluent::FluentBundle::new(vec!["en-US".parse().unwrap()]);
+        bundle.add_resource(original.clone()).unwrap();
+        let errors = bundle.add_resource(res.clone()).unwrap_err();
+        assert_eq!(errors.len(), 2);
+
+        let added = vec![("browser/en-US/main.ftl".to_string(), original)];
+        let result = add_resource_errors("app", "app/en-US/main.ftl", &res, &added, errors);
+        assert_eq!(
+            result,
+            vec![
+                L10nRegistryError::OverridingEntry {
+                    source: "app".to_string(),
+                    path: "app/en-US/main.ftl".to_string(),
+                    loc: Some((3, 1)),
+                    original_path: Some("browser/en-US/main.ftl".to_string()),
+                    kind: EntryKind::Term,
+                    id: "brand".to_string(),
+                },
+                L10nRegistryError::OverridingEntry {
+                    source: "app".to_string(),
+                    path: "app/en-US/main.ftl".to_string(),
+                    loc: Some((4, 1)),
+                    original_path: Some("browser/en-US/main.ftl".to_string()),
+                    kind: EntryKind::Message,
+                    id: "key1".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_offset_namespaces() {
+        let res = FluentResource::try_new(
+            "-brand = Firefox\nbrand =\n    brand = Value\nbrand-name = Other\n".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(entry_offset(&res, "brand", true), Some(0));
+        assert_eq!(entry_offset(&res, "brand", false), Some(17));
+        assert_eq!(entry_offset(&res, "brand-name", false), Some(43));
+        assert_eq!(entry_offset(&res, "brand-name", true), None);
+        assert_eq!(entry_offset(&res, "name", false), None);
+    }
 }
 
 #[cfg(test)]
@@ -461,8 +1227,44 @@ mod tests_tokio {
         let en_us: LanguageIdentifier = "en-US".parse().unwrap();
         let fs1 = fetcher.get_test_file_source("toolkit", vec![en_us.clone()], "toolkit/{locale}/");
 
-        let _ = fs1.fetch_file(&en_us, FTL_RESOURCE_PRESENT);
-        let file2 = fs1.fetch_file_sync(&en_us, FTL_RESOURCE_PRESENT, true);
+        drop(fs1.fetch_file(&en_us, FTL_RESOURCE_PRESENT));
+        let file2 = fs1.fetch_file_sync(&en_us, FTL_RESOURCE_PRESENT, false);
         assert!(file2.is_some());
     }
+
+    #[tokio::test]
+    async fn file_source_sync_resolves_pending_async() {
+        let fetcher = TestFileFetcher::new();
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let fs1 = fetcher.get_test_file_source("toolkit", vec![en_us.clone()], "toolkit/{locale}/");
+
+        let file1 = fs1.fetch_file(&en_us, FTL_RESOURCE_PRESENT);
+        let file2 = fs1.fetch_file(&en_us, FTL_RESOURCE_PRESENT);
+        let file3 = fs1
+            .fetch_file_sync(&en_us, FTL_RESOURCE_PRESENT, false)
+            .unwrap();
+        assert_eq!(fs1.has_file(&en_us, FTL_RESOURCE_PRESENT), Some(true));
+
+        let file1 = file1.await.unwrap();
+        let file2 = file2.await.unwrap();
+        assert!(Rc::ptr_eq(&file1, &file3));
+        assert!(Rc::ptr_eq(&file2, &file3));
+
+        let file4 = fs1.fetch_file(&en_us, FTL_RESOURCE_PRESENT).await.unwrap();
+        assert!(Rc::ptr_eq(&file4, &file3));
+    }
+
+    #[tokio::test]
+    async fn file_source_sync_resolves_pending_async_missing() {
+        let fetcher = TestFileFetcher::new();
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let fs1 = fetcher.get_test_file_source("toolkit", vec![en_us.clone()], "toolkit/{locale}/");
+
+        let file1 = fs1.fetch_file(&en_us, FTL_RESOURCE_MISSING);
+        assert!(fs1
+            .fetch_file_sync(&en_us, FTL_RESOURCE_MISSING, false)
+            .is_none());
+        assert!(file1.await.is_none());
+        assert_eq!(fs1.has_file(&en_us, FTL_RESOURCE_MISSING), Some(false));
+    }
 }
@@ -0,0 +1,243 @@
+//! A persistent cache of the resources loaded by the sources of a registry,
+//! used to avoid fetching them again on the next start.
+
+use std::{
+    fs, io,
+    path::Path,
+    str::{self, FromStr},
+};
+
+const HEADER: &str = "l10nregistry-startup-cache 1\n";
+
+/// A 64-bit FNV-1a hash, which is stable across platforms and releases.
+pub(crate) fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A resource of a source stored in a [`StartupCache`](struct.StartupCache.html).
+#[derive(Debug, Clone, PartialEq)]
+pub enum CachedResource {
+    /// The resource is missing.
+    Missing,
+    /// The raw source of the resource along with its content hash.
+    Loaded { hash: u64, source: String },
+}
+
+/// The resources cached by a single source, along with the identity of the
+/// source they have been loaded by.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceSnapshot {
+    pub name: String,
+    /// A hash of the configuration of the source. The snapshot isn't
+    /// restored into a source with a different configuration.
+    pub identity: u64,
+    /// The resources keyed by their full path.
+    pub resources: Vec<(String, CachedResource)>,
+}
+
+/// A snapshot of the caches of the sources of an `L10nRegistry`, which can
+/// be persisted and used to pre-populate the caches on the next start.
+///
+/// The raw sources of the loaded resources and the paths of the missing
+/// resources are stored. Restored resources are parsed again, but don't have
+/// to be fetched, and the bundle generators resolve the availability of the
+/// restored resources without any I/O.
+///
+/// See [`L10nRegistry::startup_cache`] and
+/// [`L10nRegistry::restore_startup_cache`].
+///
+/// [`L10nRegistry::startup_cache`]: ../registry/struct.L10nRegistry.html#method.startup_cache
+/// [`L10nRegistry::restore_startup_cache`]: ../registry/struct.L10nRegistry.html#method.restore_startup_cache
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StartupCache {
+    pub sources: Vec<SourceSnapshot>,
+}
+
+impl StartupCache {
+    pub fn get_source(&self, name: &str) -> Option<&SourceSnapshot> {
+        self.sources.iter().find(|source| source.name == name)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = HEADER.as_bytes().to_vec();
+        for source in &self.sources {
+            result.extend(
+                format!(
+                    "source {:016x} {}\n{}\n",
+                    source.identity,
+                    source.name.len(),
+                    source.name
+                )
+                .as_bytes(),
+            );
+            for (path, resource) in &source.resources {
+                match resource {
+                    CachedResource::Missing => {
+                        result.extend(format!("missing {}\n{}\n", path.len(), path).as_bytes());
+                    }
+                    CachedResource::Loaded { hash, source } => {
+                        result.extend(
+                            format!(
+                                "loaded {:016x} {} {}\n{}\n{}\n",
+                                hash,
+                                path.len(),
+                                source.len(),
+                                path,
+                                source
+                            )
+                            .as_bytes(),
+                        );
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Parse a cache serialized with [`to_bytes`](#method.to_bytes).
+    ///
+    /// Fails with `io::ErrorKind::InvalidData` if the cache is malformed.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut reader = Reader(bytes);
+        if reader.read_line()? != HEADER.trim_end() {
+            return Err(invalid_data("Unknown startup cache format."));
+        }
+
+        let mut cache = Self::default();
+        while !reader.0.is_empty() {
+            let line = reader.read_line()?;
+            let mut fields = line.split(' ');
+            match fields.next() {
+                Some("source") => {
+                    let identity = parse_hash(fields.next())?;
+                    let name_len = parse_field(fields.next())?;
+                    cache.sources.push(SourceSnapshot {
+                        name: reader.read_string(name_len)?,
+                        identity,
+                        resources: vec![],
+                    });
+                }
+                Some(kind @ "missing") | Some(kind @ "loaded") => {
+                    let source = cache
+                        .sources
+                        .last_mut()
+                        .ok_or_else(|| invalid_data("Resource listed before any source."))?;
+                    let resource = if kind == "missing" {
+                        let path_len = parse_field(fields.next())?;
+                        (reader.read_string(path_len)?, CachedResource::Missing)
+                    } else {
+                        let hash = parse_hash(fields.next())?;
+                        let path_len = parse_field(fields.next())?;
+                        let source_len = parse_field(fields.next())?;
+                        let path = reader.read_string(path_len)?;
+                        let source = reader.read_string(source_len)?;
+                        (path, CachedResource::Loaded { hash, source })
+                    };
+                    source.resources.push(resource);
+                }
+                _ => return Err(invalid_data("Unknown startup cache entry.")),
+            }
+        }
+        Ok(cache)
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_field<T: FromStr>(field: Option<&str>) -> io::Result<T> {
+    field
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| invalid_data("Malformed startup cache entry."))
+}
+
+fn parse_hash(field: Option<&str>) -> io::Result<u64> {
+    field
+        .and_then(|field| u64::from_str_radix(field, 16).ok())
+        .ok_or_else(|| invalid_data("Malformed startup cache entry."))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn read_line(&mut self) -> io::Result<&'a str> {
+        let end = self
+            .0
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| invalid_data("Unexpected end of startup cache."))?;
+        let line = str::from_utf8(&self.0[..end]).map_err(|_| invalid_data("Invalid UTF-8."))?;
+        self.0 = &self.0[end + 1..];
+        Ok(line)
+    }
+
+    /// Read a string of `len` bytes followed by a new line.
+    fn read_string(&mut self, len: usize) -> io::Result<String> {
+        if self.0.len() <= len || self.0[len] != b'\n' {
+            return Err(invalid_data("Unexpected end of startup cache."));
+        }
+        let result = str::from_utf8(&self.0[..len])
+            .map_err(|_| invalid_data("Invalid UTF-8."))?
+            .to_string();
+        self.0 = &self.0[len + 1..];
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_cache_roundtrip() {
+        let source = "key = Value\nkey2 = Line\n  with a new line\n".to_string();
+        let cache = StartupCache {
+            sources: vec![
+                SourceSnapshot {
+                    name: "app".to_string(),
+                    identity: 0x1234,
+                    resources: vec![
+                        (
+                            "en-US/main.ftl".to_string(),
+                            CachedResource::Loaded {
+                                hash: content_hash(source.as_bytes()),
+                                source,
+                            },
+                        ),
+                        ("en-US/missing.ftl".to_string(), CachedResource::Missing),
+                    ],
+                },
+                SourceSnapshot {
+                    name: "empty source".to_string(),
+                    identity: u64::MAX,
+                    resources: vec![],
+                },
+            ],
+        };
+
+        let bytes = cache.to_bytes();
+        assert_eq!(StartupCache::from_bytes(&bytes).unwrap(), cache);
+
+        assert_eq!(
+            StartupCache::from_bytes(&bytes[..bytes.len() - 20])
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            StartupCache::from_bytes(b"cache 2\n").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
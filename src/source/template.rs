@@ -0,0 +1,437 @@
+use crate::errors::L10nRegistrySetupError;
+
+use std::{convert::TryFrom, fmt, str::FromStr};
+use unic_langid::LanguageIdentifier;
+
+/// The separators an optional subtag takes along when it's left out.
+const SEPARATORS: &[&str] = &["/", "-", "_", "."];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Locale,
+    LocaleUnderscore,
+    LocaleLowercase,
+    Language,
+    /// The script, along with the separator before or after it, which is
+    /// left out with the script.
+    Script {
+        before: &'static str,
+        after: &'static str,
+    },
+    /// The region, with its separator, as for the `Script`.
+    Region {
+        before: &'static str,
+        after: &'static str,
+    },
+    ResId,
+}
+
+impl Segment {
+    fn from_placeholder(name: &str) -> Option<Self> {
+        Some(match name {
+            "locale" => Self::Locale,
+            "locale_underscore" => Self::LocaleUnderscore,
+            "locale_lowercase" => Self::LocaleLowercase,
+            "language" => Self::Language,
+            "script" => Self::Script {
+                before: "",
+                after: "",
+            },
+            "region" => Self::Region {
+                before: "",
+                after: "",
+            },
+            "res_id" => Self::ResId,
+            _ => return None,
+        })
+    }
+}
+
+/// A compiled template of the paths of the resources of a [`FileSource`],
+/// such as `"browser/{locale}/"`.
+///
+/// The template supports the following placeholders, shown for the locale
+/// `sr-Cyrl-RS`:
+///
+/// | Placeholder           | Value        |
+/// |-----------------------|--------------|
+/// | `{locale}`            | `sr-Cyrl-RS` |
+/// | `{locale_underscore}` | `sr_Cyrl_RS` |
+/// | `{locale_lowercase}`  | `sr-cyrl-rs` |
+/// | `{language}`          | `sr`         |
+/// | `{script}`            | `Cyrl`       |
+/// | `{region}`            | `RS`         |
+///
+/// For locales without a script or a region, the `{script}` and the
+/// `{region}` are left out along with the separator (`/`, `-`, `_` or `.`)
+/// before them, or after them if there is none before, so that
+/// `"{language}/{region}/"` resolves to `"de/"` for `de`.
+///
+/// The res_id of a resource replaces the `{res_id}` placeholder, or is
+/// appended to the template if there is none, so that `"{locale}/"` and
+/// `"{locale}/{res_id}"` are equivalent.
+///
+/// [`FileSource`]: struct.FileSource.html
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
+pub struct PathTemplate {
+    template: String,
+    /// The segments of the template, with exactly one `ResId`.
+    segments: Vec<Segment>,
+    /// The index of the `ResId` in the `segments`.
+    res_id_idx: usize,
+}
+
+impl PathTemplate {
+    pub fn parse(template: &str) -> Result<Self, L10nRegistrySetupError> {
+        let error = |message: &str| L10nRegistrySetupError::InvalidPathTemplate {
+            template: template.to_string(),
+            message: message.to_string(),
+        };
+
+        let mut segments = vec![];
+        let mut rest = template;
+        while !rest.is_empty() {
+            let literal_end = rest.find(['{', '}']).unwrap_or(rest.len());
+            if literal_end > 0 {
+                segments.push(Segment::Literal(rest[..literal_end].to_string()));
+                rest = &rest[literal_end..];
+                continue;
+            }
+            if rest.starts_with('}') {
+                return Err(error("Unbalanced closing brace."));
+            }
+            let end = rest
+                .find('}')
+                .ok_or_else(|| error("Unterminated placeholder."))?;
+            let name = &rest[1..end];
+            let segment = Segment::from_placeholder(name)
+                .ok_or_else(|| error(&format!("Unknown placeholder: {{{}}}.", name)))?;
+            if segment == Segment::ResId && segments.contains(&Segment::ResId) {
+                return Err(error("The {res_id} placeholder appears twice."));
+            }
+            segments.push(segment);
+            rest = &rest[end + 1..];
+        }
+        if !segments.contains(&Segment::ResId) {
+            segments.push(Segment::ResId);
+        }
+        Self::attach_separators(&mut segments);
+        let res_id_idx = segments
+            .iter()
+            .position(|segment| *segment == Segment::ResId)
+            .expect("Missing {res_id} segment");
+
+        Ok(Self {
+            template: template.to_string(),
+            segments,
+            res_id_idx,
+        })
+    }
+
+    /// Move the separators around the optional subtags from the literals
+    /// into the subtags.
+    fn attach_separators(segments: &mut Vec<Segment>) {
+        for idx in 0..segments.len() {
+            let (head, tail) = segments.split_at_mut(idx);
+            let (segment, tail) = tail.split_first_mut().expect("Missing segment");
+            let (before, after) = match segment {
+                Segment::Script { before, after } | Segment::Region { before, after } => {
+                    (before, after)
+                }
+                _ => continue,
+            };
+            if let Some(Segment::Literal(literal)) = head.last_mut() {
+                if let Some(sep) = SEPARATORS.iter().find(|sep| literal.ends_with(**sep)) {
+                    literal.truncate(literal.len() - sep.len());
+                    *before = sep;
+                    continue;
+                }
+            }
+            if let Some(Segment::Literal(literal)) = tail.first_mut() {
+                if let Some(sep) = SEPARATORS.iter().find(|sep| literal.starts_with(**sep)) {
+                    literal.drain(..sep.len());
+                    *after = sep;
+                }
+            }
+        }
+        segments
+            .retain(|segment| !matches!(segment, Segment::Literal(literal) if literal.is_empty()));
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// The segments before and after the res_id.
+    fn split(&self) -> (&[Segment], &[Segment]) {
+        (
+            &self.segments[..self.res_id_idx],
+            &self.segments[self.res_id_idx + 1..],
+        )
+    }
+
+    /// Call `f` with each piece of the paths of `locale` the `segments` stand
+    /// for, along with whether the piece is lowercased, until it returns
+    /// `false`. Returns whether all the pieces have been visited.
+    fn visit<'a, V>(segments: &'a [Segment], locale: &'a LanguageIdentifier, mut f: V) -> bool
+    where
+        V: FnMut(&'a str, bool) -> bool,
+    {
+        for segment in segments {
+            let (sep, lowercase) = match segment {
+                Segment::Literal(literal) => {
+                    if !f(literal, false) {
+                        return false;
+                    }
+                    continue;
+                }
+                Segment::Language => {
+                    if !f(locale.language.as_str(), false) {
+                        return false;
+                    }
+                    continue;
+                }
+                Segment::Script { before, after } => {
+                    if let Some(script) = &locale.script {
+                        if !(f(before, false) && f(script.as_str(), false) && f(after, false)) {
+                            return false;
+                        }
+                    }
+                    continue;
+                }
+                Segment::Region { before, after } => {
+                    if let Some(region) = &locale.region {
+                        if !(f(before, false) && f(region.as_str(), false) && f(after, false)) {
+                            return false;
+                        }
+                    }
+                    continue;
+                }
+                Segment::ResId => continue,
+                Segment::Locale => ("-", false),
+                Segment::LocaleUnderscore => ("_", false),
+                Segment::LocaleLowercase => ("-", true),
+            };
+            let subtags = std::iter::once(locale.language.as_str())
+                .chain(locale.script.as_ref().map(|script| script.as_str()))
+                .chain(locale.region.as_ref().map(|region| region.as_str()))
+                .chain(locale.variants().map(|variant| variant.as_str()));
+            for (idx, subtag) in subtags.enumerate() {
+                if (idx > 0 && !f(sep, false)) || !f(subtag, lowercase) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn push_segments(result: &mut String, segments: &[Segment], locale: &LanguageIdentifier) {
+        Self::visit(segments, locale, |piece, lowercase| {
+            if lowercase {
+                result.extend(piece.chars().map(|c| c.to_ascii_lowercase()));
+            } else {
+                result.push_str(piece);
+            }
+            true
+        });
+    }
+
+    /// Strip the pieces of the `segments` for `locale` from the start of
+    /// `path`, if it starts with them.
+    fn strip_segments<'p>(
+        segments: &[Segment],
+        locale: &LanguageIdentifier,
+        path: &'p str,
+    ) -> Option<&'p str> {
+        let mut rest = path;
+        let matches = Self::visit(segments, locale, |piece, lowercase| {
+            let head = match rest.get(..piece.len()) {
+                Some(head) => head,
+                None => return false,
+            };
+            let matches = if lowercase {
+                head.bytes()
+                    .zip(piece.bytes())
+                    .all(|(a, b)| a == b.to_ascii_lowercase())
+            } else {
+                head == piece
+            };
+            rest = &rest[piece.len()..];
+            matches
+        });
+        if matches {
+            Some(rest)
+        } else {
+            None
+        }
+    }
+
+    /// The directory of the resources of `locale`, i.e. the part of their
+    /// paths before the res_id, up to the last `/`.
+    pub fn base_dir(&self, locale: &LanguageIdentifier) -> String {
+        let mut dir = String::new();
+        Self::push_segments(&mut dir, self.split().0, locale);
+        dir.truncate(dir.rfind('/').map_or(0, |idx| idx + 1));
+        dir
+    }
+
+    /// Resolve the full path of the resource `res_id` for `locale`.
+    pub fn resolve(&self, locale: &LanguageIdentifier, res_id: &str) -> String {
+        let (prefix, suffix) = self.split();
+        let mut path = String::with_capacity(self.template.len() + res_id.len());
+        Self::push_segments(&mut path, prefix, locale);
+        path.push_str(res_id);
+        Self::push_segments(&mut path, suffix, locale);
+        path
+    }
+
+    /// Extract the res_id from the `full_path` of a resource of `locale`, if
+    /// it matches the template.
+    pub fn match_path<'p>(
+        &self,
+        locale: &LanguageIdentifier,
+        full_path: &'p str,
+    ) -> Option<&'p str> {
+        let (prefix, suffix) = self.split();
+        let rest = Self::strip_segments(prefix, locale, full_path)?;
+        let mut suffix_len = 0;
+        Self::visit(suffix, locale, |piece, _| {
+            suffix_len += piece.len();
+            true
+        });
+        let res_id_len = rest.len().checked_sub(suffix_len)?;
+        let res_id = rest.get(..res_id_len).filter(|res_id| !res_id.is_empty())?;
+        match Self::strip_segments(suffix, locale, &rest[res_id_len..]) {
+            Some("") => Some(res_id),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PathTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+impl fmt::Debug for PathTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.template, f)
+    }
+}
+
+impl FromStr for PathTemplate {
+    type Err = L10nRegistrySetupError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        Self::parse(template)
+    }
+}
+
+impl TryFrom<String> for PathTemplate {
+    type Error = L10nRegistrySetupError;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        Self::parse(&template)
+    }
+}
+
+impl From<PathTemplate> for String {
+    fn from(template: PathTemplate) -> Self {
+        template.template
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_template_resolve() {
+        let sr: LanguageIdentifier = "sr-Cyrl-RS".parse().unwrap();
+        let de: LanguageIdentifier = "de".parse().unwrap();
+
+        let template = PathTemplate::parse("browser/{locale}/").unwrap();
+        assert_eq!(
+            template.resolve(&sr, "menu.ftl"),
+            "browser/sr-Cyrl-RS/menu.ftl"
+        );
+        assert_eq!(template.to_string(), "browser/{locale}/");
+
+        let template = PathTemplate::parse("{language}/{region}/{res_id}").unwrap();
+        assert_eq!(template.resolve(&sr, "menu.ftl"), "sr/RS/menu.ftl");
+        assert_eq!(template.resolve(&de, "menu.ftl"), "de/menu.ftl");
+        assert_eq!(template.match_path(&de, "de/menu.ftl"), Some("menu.ftl"));
+        assert_eq!(template.match_path(&sr, "sr/menu.ftl"), None);
+        assert_eq!(template.base_dir(&sr), "sr/RS/");
+
+        let template =
+            PathTemplate::parse("l10n/{res_id}/{locale_underscore}.{locale_lowercase}.ftl")
+                .unwrap();
+        assert_eq!(
+            template.resolve(&sr, "menu"),
+            "l10n/menu/sr_Cyrl_RS.sr-cyrl-rs.ftl"
+        );
+        assert_eq!(
+            template.match_path(&sr, "l10n/panels/about/sr_Cyrl_RS.sr-cyrl-rs.ftl"),
+            Some("panels/about")
+        );
+        assert_eq!(
+            template.match_path(&de, "l10n/menu/sr_Cyrl_RS.sr-cyrl-rs.ftl"),
+            None
+        );
+
+        let template = PathTemplate::parse("{script}-{language}/").unwrap();
+        assert_eq!(template.resolve(&sr, "menu.ftl"), "Cyrl-sr/menu.ftl");
+        assert_eq!(template.resolve(&de, "menu.ftl"), "de/menu.ftl");
+
+        let template = PathTemplate::parse("{language}/{script}/{region}/").unwrap();
+        let sr_rs: LanguageIdentifier = "sr-RS".parse().unwrap();
+        assert_eq!(template.resolve(&sr, "menu.ftl"), "sr/Cyrl/RS/menu.ftl");
+        assert_eq!(template.resolve(&sr_rs, "menu.ftl"), "sr/RS/menu.ftl");
+        assert_eq!(template.resolve(&de, "menu.ftl"), "de/menu.ftl");
+
+        let template = PathTemplate::parse("browser-{locale_lowercase}/").unwrap();
+        assert_eq!(
+            template.match_path(&sr, "browser-sr-cyrl-rs/menu.ftl"),
+            Some("menu.ftl")
+        );
+        assert_eq!(
+            template.match_path(&sr, "browser-sr-Cyrl-RS/menu.ftl"),
+            None
+        );
+        assert_eq!(template.base_dir(&sr), "browser-sr-cyrl-rs/");
+    }
+
+    #[test]
+    fn path_template_errors() {
+        let error = |template: &str, message: &str| {
+            Err(L10nRegistrySetupError::InvalidPathTemplate {
+                template: template.to_string(),
+                message: message.to_string(),
+            })
+        };
+        assert_eq!(
+            PathTemplate::parse("browser/{lang}/"),
+            error("browser/{lang}/", "Unknown placeholder: {lang}.")
+        );
+        assert_eq!(
+            PathTemplate::parse("browser/{locale/"),
+            error("browser/{locale/", "Unterminated placeholder.")
+        );
+        assert_eq!(
+            PathTemplate::parse("browser/locale}/"),
+            error("browser/locale}/", "Unbalanced closing brace.")
+        );
+        assert_eq!(
+            PathTemplate::parse("{res_id}/{locale}/{res_id}"),
+            error(
+                "{res_id}/{locale}/{res_id}",
+                "The {res_id} placeholder appears twice."
+            )
+        );
+    }
+}
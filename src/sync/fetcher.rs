@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+use std::io;
+
+/// A thread-safe counterpart of [`FileFetcher`], used by the sources of the
+/// [`Concurrent`](struct.Concurrent.html) family.
+///
+/// [`FileFetcher`]: ../source/trait.FileFetcher.html
+#[async_trait]
+pub trait SendFileFetcher: Send + Sync {
+    /// Return the `String` representation for `path`. This version is
+    /// blocking.
+    ///
+    /// See [`fetch`](#tymethod.fetch).
+    fn fetch_sync(&self, path: &str) -> io::Result<String>;
+
+    /// Return the `String` representation for `path`.
+    ///
+    /// See [`fetch_sync`](#tymethod.fetch_sync)
+    async fn fetch(&self, path: &str) -> io::Result<String>;
+}
@@ -0,0 +1,188 @@
+//! Thread-safe counterparts of [`FileSource`] and [`L10nRegistry`], built on
+//! top of `Arc` and `RwLock`.
+//!
+//! They are the sources and the registries of the [`Concurrent`] family, and
+//! share all their logic with their single-threaded counterparts. By default,
+//! they generate concurrent `FluentBundle`s, so that a registry can be
+//! shared across threads and used from tasks spawned on a multi-threaded
+//! runtime.
+//!
+//! A registry of the `Concurrent<intl_memoizer::IntlLangMemoizer>` family
+//! generates the bundles of `fluent_fallback` instead, so that it can be used as a
+//! `BundleGenerator` while its sources are shared across threads.
+//!
+//! [`FileSource`]: ../source/struct.FileSource.html
+//! [`L10nRegistry`]: ../registry/struct.L10nRegistry.html
+//! [`Concurrent`]: struct.Concurrent.html
+
+mod fetcher;
+
+pub use crate::registry::BundleAdapter;
+pub use fetcher::SendFileFetcher;
+
+use crate::env::ErrorReporter;
+use crate::family::{private, Family, IntoFetcher, IntoListener, IntoReporter, Resource};
+use crate::fluent::FluentResource;
+use crate::registry::ResourceChange;
+use crate::source::FileSource as LocalFileSource;
+
+use std::{
+    io,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use fluent_bundle::bundle;
+use futures::{
+    channel::oneshot,
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+use intl_memoizer::concurrent::IntlLangMemoizer;
+use unic_langid::LanguageIdentifier;
+
+pub type FluentBundle = bundle::FluentBundle<Arc<FluentResource>, IntlLangMemoizer>;
+
+pub type ArcResource = Arc<FluentResource>;
+pub type ResourceOption = Option<ArcResource>;
+pub type ResourceFuture = Shared<BoxFuture<'static, ResourceOption>>;
+
+pub type FileSource = crate::source::FileSource<Concurrent>;
+pub type ResourceStatus = crate::source::ResourceStatus<Concurrent>;
+pub type L10nRegistry<P, B> = crate::registry::L10nRegistry<P, B, Concurrent>;
+pub type L10nRegistryLocked<'a, B> = crate::registry::L10nRegistryLocked<'a, B, Concurrent>;
+pub type GenerateBundles<P, B> = crate::registry::GenerateBundles<P, B, Concurrent>;
+pub type GenerateBundlesSync<P, B> = crate::registry::GenerateBundlesSync<P, B, Concurrent>;
+
+/// The family of the sources and the registries which can be shared across
+/// threads, generating bundles with the memoizer `M`.
+pub struct Concurrent<M = IntlLangMemoizer>(PhantomData<M>);
+
+impl<M: private::BundleMemoizer> Family for Concurrent<M> {
+    type Ptr<T> = Arc<T>;
+    type Lock<T> = RwLock<T>;
+    type Counter = AtomicUsize;
+    type Fetcher = dyn SendFileFetcher;
+    type Reporter = dyn ErrorReporter + Send + Sync;
+    type Listener = Arc<dyn Fn(&[ResourceChange]) + Send + Sync>;
+    type Memoizer = M;
+    type ResourceFuture = ResourceFuture;
+}
+
+impl<M: private::BundleMemoizer> private::Sealed for Concurrent<M> {
+    fn load_resource(
+        source: &LocalFileSource<Self>,
+        path: String,
+        load_id: usize,
+        sync_result: oneshot::Receiver<Option<Resource<Self>>>,
+    ) -> <Self as Family>::ResourceFuture {
+        source
+            .load_resource(path, load_id, sync_result)
+            .boxed()
+            .shared()
+    }
+}
+
+impl<T: SendFileFetcher + 'static, M: private::BundleMemoizer> IntoFetcher<Concurrent<M>> for T {
+    fn into_fetcher(self) -> Box<dyn SendFileFetcher> {
+        Box::new(self)
+    }
+}
+
+impl<T, M> IntoReporter<Concurrent<M>> for T
+where
+    T: ErrorReporter + Send + Sync + 'static,
+    M: private::BundleMemoizer,
+{
+    fn into_reporter(self) -> Box<dyn ErrorReporter + Send + Sync> {
+        Box::new(self)
+    }
+}
+
+impl<T, M> IntoListener<Concurrent<M>> for T
+where
+    T: Fn(&[ResourceChange]) + Send + Sync + 'static,
+    M: private::BundleMemoizer,
+{
+    fn into_listener(self) -> Arc<dyn Fn(&[ResourceChange]) + Send + Sync> {
+        Arc::new(self)
+    }
+}
+
+impl<T> private::Pointer<T> for Arc<T> {
+    fn new(value: T) -> Self {
+        Arc::new(value)
+    }
+
+    fn get_mut(this: &mut Self) -> Option<&mut T> {
+        Arc::get_mut(this)
+    }
+}
+
+impl<T> private::Lock<T> for RwLock<T> {
+    type ReadGuard<'a>
+        = RwLockReadGuard<'a, T>
+    where
+        T: 'a;
+    type WriteGuard<'a>
+        = RwLockWriteGuard<'a, T>
+    where
+        T: 'a;
+
+    fn new(value: T) -> Self {
+        RwLock::new(value)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, T> {
+        RwLock::read(self).expect("[l10nregistry] A lock has been poisoned.")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, T> {
+        RwLock::write(self).expect("[l10nregistry] A lock has been poisoned.")
+    }
+
+    fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        RwLock::read(self).ok()
+    }
+
+    fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        RwLock::write(self).ok()
+    }
+}
+
+impl private::Counter for AtomicUsize {
+    fn get(&self) -> usize {
+        self.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, value: usize) {
+        self.store(value, Ordering::Relaxed)
+    }
+}
+
+impl private::Fetcher for dyn SendFileFetcher {
+    type Future<'a> = BoxFuture<'a, io::Result<String>>;
+
+    fn fetch_sync(&self, path: &str) -> io::Result<String> {
+        SendFileFetcher::fetch_sync(self, path)
+    }
+
+    fn fetch<'a>(&'a self, path: &'a str) -> Self::Future<'a> {
+        SendFileFetcher::fetch(self, path)
+    }
+}
+
+impl private::Listener for Arc<dyn Fn(&[ResourceChange]) + Send + Sync> {
+    fn call(&self, changes: &[ResourceChange]) {
+        self(changes)
+    }
+}
+
+impl private::BundleMemoizer for IntlLangMemoizer {
+    fn new_bundle<R>(locales: Vec<LanguageIdentifier>) -> bundle::FluentBundle<R, Self> {
+        bundle::FluentBundle::new_concurrent(locales)
+    }
+}
@@ -110,16 +110,14 @@ struct InnerFileFetcher {
     fs: MockFileSystem,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct TestFileFetcher {
     inner: Rc<InnerFileFetcher>,
 }
 
 impl TestFileFetcher {
     pub fn new() -> Self {
-        Self {
-            inner: Rc::new(InnerFileFetcher::default()),
-        }
+        Self::default()
     }
 
     pub fn get_test_file_source(
@@ -131,7 +129,7 @@ impl TestFileFetcher {
         crate::source::FileSource::new(
             name.to_string(),
             locales,
-            path.to_string(),
+            path.parse().unwrap(),
             Default::default(),
             self.clone(),
         )
@@ -147,7 +145,7 @@ impl TestFileFetcher {
         crate::source::FileSource::new_with_index(
             name.to_string(),
             locales,
-            path.to_string(),
+            path.parse().unwrap(),
             Default::default(),
             self.clone(),
             index.into_iter().map(|s| s.to_string()).collect(),
@@ -181,7 +179,8 @@ impl TestFileFetcher {
             .map(|source| {
                 let mut s =
                     self.get_test_file_source(&source.name, source.locales, &source.path_scheme);
-                s.set_reporter(provider.clone());
+                s.set_reporter(provider.clone())
+                    .expect("Failed to set the reporter.");
                 s
             })
             .collect();
@@ -208,7 +207,8 @@ impl TestFileFetcher {
             .map(|source| {
                 let mut s =
                     self.get_test_file_source(&source.name, source.locales, &source.path_scheme);
-                s.set_reporter(provider.clone());
+                s.set_reporter(provider.clone())
+                    .expect("Failed to set the reporter.");
                 s
             })
             .collect();
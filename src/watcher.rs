@@ -0,0 +1,244 @@
+//! Hot reloading of resources for sources backed by a filesystem.
+//!
+//! A [`RegistryWatcher`] watches the directories of all the sources of an
+//! [`L10nRegistry`], evicts the changed resources from the caches of the
+//! sources, and notifies the listeners registered with
+//! [`L10nRegistry::add_change_listener`].
+//!
+//! The detection of changes is delegated to a [`WatcherBackend`].
+//! [`PollBackend`] is a portable backend which scans the watched directories.
+//!
+//! [`RegistryWatcher`]: struct.RegistryWatcher.html
+//! [`L10nRegistry`]: ../registry/struct.L10nRegistry.html
+//! [`L10nRegistry::add_change_listener`]: ../registry/struct.L10nRegistry.html#method.add_change_listener
+//! [`WatcherBackend`]: trait.WatcherBackend.html
+//! [`PollBackend`]: struct.PollBackend.html
+
+use crate::family::{Family, Local};
+use crate::registry::{L10nRegistry, ResourceChange};
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// A backend reporting changes of files in the watched directories.
+pub trait WatcherBackend {
+    /// Start watching `dir` and all its subdirectories.
+    ///
+    /// The directory doesn't have to exist yet.
+    fn watch(&mut self, dir: &Path);
+
+    /// Return the files which have been created, modified or removed since
+    /// the last call.
+    fn changes(&mut self) -> Vec<PathBuf>;
+}
+
+/// A [`WatcherBackend`](trait.WatcherBackend.html) which detects changes by
+/// scanning the watched directories and comparing the modification time and
+/// the size of the files with the previous scan.
+#[derive(Debug, Default)]
+pub struct PollBackend {
+    dirs: Vec<PathBuf>,
+    snapshot: HashMap<PathBuf, (Option<SystemTime>, u64)>,
+}
+
+impl PollBackend {
+    fn scan(dir: &Path, snapshot: &mut HashMap<PathBuf, (Option<SystemTime>, u64)>) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir() {
+                Self::scan(&entry.path(), snapshot);
+            } else {
+                snapshot.insert(entry.path(), (metadata.modified().ok(), metadata.len()));
+            }
+        }
+    }
+}
+
+impl WatcherBackend for PollBackend {
+    fn watch(&mut self, dir: &Path) {
+        Self::scan(dir, &mut self.snapshot);
+        self.dirs.push(dir.to_path_buf());
+    }
+
+    fn changes(&mut self) -> Vec<PathBuf> {
+        let mut snapshot = HashMap::new();
+        for dir in &self.dirs {
+            Self::scan(dir, &mut snapshot);
+        }
+
+        let mut result: Vec<PathBuf> = snapshot
+            .iter()
+            .filter(|(path, state)| self.snapshot.get(*path) != Some(*state))
+            .map(|(path, _)| path.clone())
+            .chain(
+                self.snapshot
+                    .keys()
+                    .filter(|path| !snapshot.contains_key(*path))
+                    .cloned(),
+            )
+            .collect();
+        result.sort();
+
+        self.snapshot = snapshot;
+        result
+    }
+}
+
+/// Watches the directories of the sources of an `L10nRegistry` whose
+/// fetchers read files relative to `root`, such as a
+/// [`FsFileFetcher`](../source/fs/struct.FsFileFetcher.html).
+pub struct RegistryWatcher<P, B, W, F: Family = Local> {
+    registry: L10nRegistry<P, B, F>,
+    root: PathBuf,
+    backend: W,
+    watched: HashSet<PathBuf>,
+}
+
+impl<P, B, W, F> RegistryWatcher<P, B, W, F>
+where
+    W: WatcherBackend,
+    F: Family,
+{
+    pub fn new<R>(registry: L10nRegistry<P, B, F>, root: R, backend: W) -> Self
+    where
+        R: Into<PathBuf>,
+    {
+        let mut watcher = Self {
+            registry,
+            root: root.into(),
+            backend,
+            watched: HashSet::new(),
+        };
+        watcher.watch_sources();
+        watcher
+    }
+
+    /// Start watching the directories of the sources registered since the
+    /// watcher has been created.
+    pub fn watch_sources(&mut self) {
+        let lock = self.registry.lock();
+        for source in lock.iter() {
+            for locale in source.locales() {
+                let dir = self.root.join(source.pre_path.base_dir(locale));
+                if self.watched.insert(dir.clone()) {
+                    self.backend.watch(&dir);
+                }
+            }
+        }
+    }
+
+    /// Evict the changed resources from the caches of the sources and notify
+    /// the listeners of the registry. Returns the list of changed resources.
+    pub fn poll(&mut self) -> Vec<ResourceChange> {
+        let mut changes = vec![];
+        {
+            let lock = self.registry.lock();
+            for path in self.backend.changes() {
+                let full_path = match path.strip_prefix(&self.root) {
+                    Ok(path) => path
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                    Err(_) => continue,
+                };
+                for source in lock.iter() {
+                    for locale in source.locales() {
+                        if let Some(res_id) = source.pre_path.match_path(locale, &full_path) {
+                            source.invalidate(locale, res_id);
+                            changes.push(ResourceChange {
+                                source: source.name.clone(),
+                                locale: locale.clone(),
+                                res_id: res_id.to_string(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        if !changes.is_empty() {
+            self.registry.notify_changes(&changes);
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{fs::FsFileFetcher, FileSource};
+    use std::{cell::RefCell, rc::Rc};
+    use unic_langid::LanguageIdentifier;
+
+    #[test]
+    fn registry_watcher_poll() {
+        let dir = std::env::temp_dir().join(format!("l10nregistry-watcher-{}", std::process::id()));
+        fs::create_dir_all(dir.join("app/en-US")).unwrap();
+        fs::write(dir.join("app/en-US/main.ftl"), "key = Value\n").unwrap();
+
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let source = FileSource::new(
+            "app".to_string(),
+            vec![en_us.clone()],
+            "app/{locale}/".parse().unwrap(),
+            Default::default(),
+            FsFileFetcher::new(&dir),
+        );
+        let reg: L10nRegistry<(), ()> = L10nRegistry::with_provider(());
+        reg.register_sources(vec![source]).unwrap();
+
+        let notified = Rc::new(RefCell::new(vec![]));
+        let listener_notified = notified.clone();
+        reg.add_change_listener(move |changes| {
+            listener_notified.borrow_mut().extend_from_slice(changes);
+        });
+
+        let mut watcher = RegistryWatcher::new(reg.clone(), &dir, PollBackend::default());
+        assert_eq!(watcher.poll(), vec![]);
+
+        {
+            let lock = reg.lock();
+            let source = lock.get_source("app").unwrap();
+            assert!(source.fetch_file_sync(&en_us, "main.ftl", false).is_some());
+            assert_eq!(source.has_file(&en_us, "main.ftl"), Some(true));
+        }
+
+        fs::write(
+            dir.join("app/en-US/main.ftl"),
+            "key = Value\nkey2 = Value 2\n",
+        )
+        .unwrap();
+
+        let expected = vec![ResourceChange {
+            source: "app".to_string(),
+            locale: en_us.clone(),
+            res_id: "main.ftl".to_string(),
+        }];
+        assert_eq!(watcher.poll(), expected);
+        assert_eq!(*notified.borrow(), expected);
+
+        {
+            let lock = reg.lock();
+            let source = lock.get_source("app").unwrap();
+            assert_eq!(source.has_file(&en_us, "main.ftl"), None);
+            let res = source.fetch_file_sync(&en_us, "main.ftl", false).unwrap();
+            assert_eq!(res.source(), "key = Value\nkey2 = Value 2\n");
+        }
+
+        assert_eq!(watcher.poll(), vec![]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,8 +1,18 @@
-use l10nregistry::testing::{FileSource, RegistrySetup, TestFileFetcher};
+use l10nregistry::errors::{L10nRegistryError, L10nRegistrySetupError, LookupStatus, SourceLookup};
+use l10nregistry::registry::{
+    BundleMetadata, L10nRegistry, LocaleFallback, NegotiationStrategy, RegistryConfig,
+    ResourceChange, ResourceId, ResourceOrigin,
+};
+use l10nregistry::solver::SolverOptions;
+use l10nregistry::source::{FileSourceDescription, StartupCache};
+use l10nregistry::testing::{
+    FileSource, MockBundleAdapter, RegistrySetup, TestEnvironment, TestFileFetcher,
+};
 use unic_langid::LanguageIdentifier;
 
 const FTL_RESOURCE_TOOLKIT: &str = "toolkit/global/textActions.ftl";
 const FTL_RESOURCE_BROWSER: &str = "branding/brand.ftl";
+const FTL_RESOURCE_MISSING: &str = "missing.ftl";
 
 #[test]
 fn test_generate_sources_for_file() {
@@ -62,7 +72,7 @@ fn test_generate_bundles_for_lang_sync() {
     let fetcher = TestFileFetcher::new();
     let (_, reg) = fetcher.get_registry_and_environment(setup);
 
-    let paths = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_BROWSER.into()];
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
     let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths);
 
     assert!(i.next().is_some());
@@ -83,7 +93,10 @@ fn test_generate_bundles_sync() {
     let fetcher = TestFileFetcher::new();
     let (_, reg) = fetcher.get_registry_and_environment(setup);
 
-    let paths = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_BROWSER.into()];
+    let paths = vec![
+        FTL_RESOURCE_TOOLKIT.to_string(),
+        FTL_RESOURCE_BROWSER.to_string(),
+    ];
     let lang_ids = vec![en_us];
     let mut i = reg.generate_bundles_sync(lang_ids.into_iter(), paths);
 
@@ -107,7 +120,7 @@ async fn test_generate_bundles_for_lang() {
     let fetcher = TestFileFetcher::new();
     let (_, reg) = fetcher.get_registry_and_environment(setup);
 
-    let paths = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_BROWSER.into()];
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
     let mut i = reg.generate_bundles_for_lang(en_us, paths);
 
     assert!(i.next().await.is_some());
@@ -130,7 +143,7 @@ async fn test_generate_bundles() {
     let fetcher = TestFileFetcher::new();
     let (_, reg) = fetcher.get_registry_and_environment(setup);
 
-    let paths = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_BROWSER.into()];
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
     let langs = vec![en_us];
     let mut i = reg.generate_bundles(langs.into_iter(), paths);
 
@@ -138,6 +151,41 @@ async fn test_generate_bundles() {
     assert!(i.next().await.is_none());
 }
 
+#[tokio::test]
+async fn test_generate_bundles_prefetch() {
+    use fluent_fallback::generator::BundleStream;
+    use futures::stream::StreamExt;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+            FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (_, reg) = fetcher.get_registry_and_environment(setup);
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+    let langs = vec![en_us.clone()];
+    let mut i = reg.generate_bundles(langs.into_iter(), paths);
+
+    i.prefetch_async().await;
+
+    {
+        let lock = reg.lock();
+        let toolkit = lock.get_source("toolkit").unwrap();
+        let browser = lock.get_source("browser").unwrap();
+        assert_eq!(toolkit.has_file(&en_us, FTL_RESOURCE_TOOLKIT), Some(true));
+        assert_eq!(browser.has_file(&en_us, FTL_RESOURCE_BROWSER), Some(true));
+    }
+
+    assert!(i.next().await.is_some());
+    assert!(i.next().await.is_none());
+}
+
 #[test]
 fn test_manage_sources() {
     let en_us: LanguageIdentifier = "en-US".parse().unwrap();
@@ -154,7 +202,7 @@ fn test_manage_sources() {
 
     let lang_ids = vec![en_us];
 
-    let paths = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_BROWSER.into()];
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
 
     let mut i = reg.generate_bundles_sync(lang_ids.clone().into_iter(), paths);
 
@@ -165,11 +213,11 @@ fn test_manage_sources() {
         .remove_sources(vec!["toolkit"])
         .expect("Failed to remove a source.");
 
-    let paths = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_BROWSER.into()];
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
     let mut i = reg.generate_bundles_sync(lang_ids.clone().into_iter(), paths);
     assert!(i.next().is_none());
 
-    let paths = vec![FTL_RESOURCE_BROWSER.into()];
+    let paths = vec![FTL_RESOURCE_BROWSER];
     let mut i = reg.generate_bundles_sync(lang_ids.clone().into_iter(), paths);
     assert!(i.next().is_some());
     assert!(i.next().is_none());
@@ -181,7 +229,7 @@ fn test_manage_sources() {
     )])
     .expect("Failed to register a source.");
 
-    let paths = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_BROWSER.into()];
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
     let mut i = reg.generate_bundles_sync(lang_ids.clone().into_iter(), paths);
     assert!(i.next().is_none());
 
@@ -192,8 +240,717 @@ fn test_manage_sources() {
     )])
     .expect("Failed to update a source.");
 
-    let paths = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_BROWSER.into()];
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
     let mut i = reg.generate_bundles_sync(lang_ids.clone().into_iter(), paths);
     assert!(i.next().is_some());
     assert!(i.next().is_none());
 }
+
+#[test]
+fn test_invalidate_caches() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+            FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (_, reg) = fetcher.get_registry_and_environment(setup);
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+    let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths);
+    assert!(i.next().is_some());
+
+    reg.invalidate_resource(&en_us, FTL_RESOURCE_TOOLKIT)
+        .expect("Failed to invalidate a resource.");
+    {
+        let lock = reg.lock();
+        let toolkit = lock.get_source("toolkit").unwrap();
+        let browser = lock.get_source("browser").unwrap();
+        assert_eq!(toolkit.has_file(&en_us, FTL_RESOURCE_TOOLKIT), None);
+        assert_eq!(browser.has_file(&en_us, FTL_RESOURCE_BROWSER), Some(true));
+    }
+
+    reg.clear_caches().expect("Failed to clear caches.");
+    {
+        let lock = reg.lock();
+        let browser = lock.get_source("browser").unwrap();
+        assert_eq!(browser.has_file(&en_us, FTL_RESOURCE_BROWSER), None);
+    }
+
+    // The live generator retests the sources after the invalidation.
+    assert!(i.next().is_none());
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+    let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths);
+    assert!(i.next().is_some());
+    assert!(i.next().is_none());
+}
+
+#[test]
+fn test_generate_bundles_optional_sync() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+            FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (env, reg) = fetcher.get_registry_and_environment(setup);
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_MISSING];
+    let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths);
+    assert!(i.next().is_none());
+    assert_eq!(
+        env.errors(),
+        vec![L10nRegistryError::MissingResource {
+            locale: en_us.clone(),
+            res_id: FTL_RESOURCE_MISSING.to_string(),
+            sources: vec![
+                SourceLookup {
+                    source: "browser".to_string(),
+                    template: "browser/{locale}/".to_string(),
+                    path: format!("browser/en-US/{}", FTL_RESOURCE_MISSING),
+                    status: LookupStatus::NotFound,
+                },
+                SourceLookup {
+                    source: "toolkit".to_string(),
+                    template: "toolkit/{locale}/".to_string(),
+                    path: format!("toolkit/en-US/{}", FTL_RESOURCE_MISSING),
+                    status: LookupStatus::NotFound,
+                },
+            ],
+        }]
+    );
+    env.clear_errors();
+
+    let paths = vec![
+        FTL_RESOURCE_TOOLKIT.into(),
+        ResourceId::optional(FTL_RESOURCE_MISSING),
+    ];
+    let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths);
+    assert!(i.next().unwrap().is_ok());
+    assert!(i.next().is_none());
+    assert_eq!(
+        env.errors(),
+        vec![L10nRegistryError::MissingOptionalResource {
+            locale: en_us.clone(),
+            res_id: FTL_RESOURCE_MISSING.to_string(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_generate_bundles_optional() {
+    use futures::stream::StreamExt;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+            FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (env, reg) = fetcher.get_registry_and_environment(setup);
+
+    let paths = vec![
+        ResourceId::optional(FTL_RESOURCE_TOOLKIT),
+        ResourceId::optional(FTL_RESOURCE_MISSING),
+    ];
+    let mut i = reg.generate_bundles_for_lang(en_us.clone(), paths);
+    assert!(i.next().await.unwrap().is_ok());
+    assert!(i.next().await.is_none());
+    assert_eq!(
+        env.errors(),
+        vec![L10nRegistryError::MissingOptionalResource {
+            locale: en_us.clone(),
+            res_id: FTL_RESOURCE_MISSING.to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_generate_bundles_metadata_sync() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+            FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (_, reg) = fetcher.get_registry_and_environment(setup);
+
+    let paths = vec![
+        FTL_RESOURCE_TOOLKIT.into(),
+        FTL_RESOURCE_BROWSER.into(),
+        ResourceId::optional(FTL_RESOURCE_MISSING),
+    ];
+    let mut i = reg
+        .generate_bundles_for_lang_sync(en_us.clone(), paths)
+        .with_metadata();
+
+    let (bundle, metadata) = i.next().unwrap();
+    assert!(bundle.is_ok());
+    assert_eq!(
+        metadata,
+        BundleMetadata {
+            locale: en_us.clone(),
+            resources: vec![
+                ResourceOrigin {
+                    res_id: FTL_RESOURCE_TOOLKIT.to_string(),
+                    source: "toolkit".to_string(),
+                    path: format!("toolkit/en-US/{}", FTL_RESOURCE_TOOLKIT),
+                },
+                ResourceOrigin {
+                    res_id: FTL_RESOURCE_BROWSER.to_string(),
+                    source: "browser".to_string(),
+                    path: format!("browser/en-US/{}", FTL_RESOURCE_BROWSER),
+                },
+            ],
+        }
+    );
+    assert!(i.next().is_none());
+}
+
+#[tokio::test]
+async fn test_generate_bundles_metadata() {
+    use futures::stream::StreamExt;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+            FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (_, reg) = fetcher.get_registry_and_environment(setup);
+
+    let paths = vec![FTL_RESOURCE_BROWSER];
+    let mut i = reg
+        .generate_bundles_for_lang(en_us.clone(), paths)
+        .with_metadata();
+
+    let (bundle, metadata) = i.next().await.unwrap();
+    assert!(bundle.is_ok());
+    assert_eq!(metadata.locale, en_us);
+    assert_eq!(
+        metadata.resources,
+        vec![ResourceOrigin {
+            res_id: FTL_RESOURCE_BROWSER.to_string(),
+            source: "browser".to_string(),
+            path: format!("browser/en-US/{}", FTL_RESOURCE_BROWSER),
+        }]
+    );
+    assert!(i.next().await.is_none());
+}
+
+#[test]
+fn test_source_priority() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher = TestFileFetcher::new();
+
+    let mut langpack =
+        fetcher.get_test_file_source("langpack", vec![en_us.clone()], "browser/{locale}/");
+    langpack.options.priority = 10;
+    let mut app = fetcher.get_test_file_source("app", vec![en_us.clone()], "browser/{locale}/");
+    app.options.priority = 5;
+    let toolkit = fetcher.get_test_file_source("toolkit", vec![en_us.clone()], "toolkit/{locale}/");
+
+    let reg: L10nRegistry<(), ()> = L10nRegistry::with_provider(());
+    reg.register_sources(vec![langpack.clone(), app]).unwrap();
+    reg.register_sources(vec![toolkit]).unwrap();
+
+    let source_order = |reg: &L10nRegistry<(), ()>| -> Vec<String> {
+        let lock = reg.lock();
+        (0..lock.len())
+            .map(|idx| lock.source_idx(idx).name.clone())
+            .collect()
+    };
+    assert_eq!(source_order(&reg), vec!["langpack", "app", "toolkit"]);
+
+    langpack.options.priority = 0;
+    reg.update_sources(vec![langpack]).unwrap();
+    assert_eq!(source_order(&reg), vec!["app", "langpack", "toolkit"]);
+}
+
+#[test]
+fn test_startup_cache() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = || {
+        RegistrySetup::new(
+            "test",
+            vec![
+                FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+                FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+            ],
+            vec![en_us.clone()],
+        )
+    };
+    let fetcher = TestFileFetcher::new();
+    let (_, reg) = fetcher.get_registry_and_environment(setup());
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+    let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths);
+    assert!(i.next().is_some());
+
+    let bytes = reg.startup_cache().unwrap().to_bytes();
+    let cache = StartupCache::from_bytes(&bytes).unwrap();
+
+    let (_, reg) = fetcher.get_registry_and_environment(setup());
+    assert_eq!(reg.restore_startup_cache(&cache), Ok(3));
+    {
+        let lock = reg.lock();
+        let toolkit = lock.get_source("toolkit").unwrap();
+        let browser = lock.get_source("browser").unwrap();
+        assert_eq!(toolkit.has_file(&en_us, FTL_RESOURCE_TOOLKIT), Some(true));
+        assert_eq!(browser.has_file(&en_us, FTL_RESOURCE_TOOLKIT), Some(false));
+        assert_eq!(browser.has_file(&en_us, FTL_RESOURCE_BROWSER), Some(true));
+    }
+    assert_eq!(reg.restore_startup_cache(&cache), Ok(0));
+
+    // A source with a different configuration doesn't restore the snapshot.
+    let setup = RegistrySetup::new(
+        "test",
+        vec![FileSource::new(
+            "toolkit",
+            vec![en_us.clone(), "pl".parse().unwrap()],
+            "toolkit/{locale}/",
+        )],
+        vec![en_us.clone()],
+    );
+    let (_, reg) = fetcher.get_registry_and_environment(setup);
+    assert_eq!(reg.restore_startup_cache(&cache), Ok(0));
+}
+
+#[test]
+fn test_locale_fallback() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let en_us_macos: LanguageIdentifier = "en-US-macos".parse().unwrap();
+    let setup = || {
+        RegistrySetup::new(
+            "test",
+            vec![
+                FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+                FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+            ],
+            vec![en_us.clone()],
+        )
+    };
+    let fetcher = TestFileFetcher::new();
+    let paths = || vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+
+    let (_, reg) = fetcher.get_registry_and_environment(setup());
+    let mut i = reg.generate_bundles_for_lang_sync(en_us_macos.clone(), paths());
+    assert!(i.next().is_none());
+
+    let (_, mut reg) = fetcher.get_registry_and_environment(setup());
+    reg.set_locale_fallback(LocaleFallback {
+        default_locale: Some("pl".parse().unwrap()),
+        likely_subtags: true,
+    })
+    .unwrap();
+    assert_eq!(
+        reg.expand_locales(vec![en_us_macos.clone()]),
+        vec![
+            en_us_macos.clone(),
+            en_us.clone(),
+            "en".parse().unwrap(),
+            "pl".parse().unwrap()
+        ]
+    );
+
+    let mut i = reg
+        .generate_bundles_for_lang_sync(en_us_macos.clone(), paths())
+        .with_metadata();
+    let (bundle, metadata) = i.next().unwrap();
+    assert!(bundle.is_ok());
+    assert_eq!(metadata.locale, en_us);
+    assert!(i.next().is_none());
+}
+
+#[test]
+fn test_negotiate_locales() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    let de: LanguageIdentifier = "de".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new(
+                "toolkit",
+                vec![en_us.clone(), pl.clone()],
+                "toolkit/{locale}/",
+            ),
+            FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (_, mut reg) = fetcher.get_registry_and_environment(setup);
+
+    let requested = vec![de.clone(), pl.clone(), en_us.clone()];
+    let toolkit_only = vec![FTL_RESOURCE_TOOLKIT.into()];
+    let all = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_BROWSER.into()];
+    let with_optional = vec![
+        FTL_RESOURCE_TOOLKIT.into(),
+        ResourceId::optional(FTL_RESOURCE_BROWSER),
+    ];
+
+    assert_eq!(
+        reg.negotiate_locales(&requested, Some(&en_us), NegotiationStrategy::Filtering),
+        Ok(vec![pl.clone(), en_us.clone()])
+    );
+
+    reg.set_required_resources(toolkit_only).unwrap();
+    assert_eq!(
+        reg.negotiate_locales(&requested, Some(&en_us), NegotiationStrategy::Filtering),
+        Ok(vec![pl.clone(), en_us.clone()])
+    );
+    assert_eq!(
+        reg.negotiate_locales(&requested, Some(&en_us), NegotiationStrategy::Lookup),
+        Ok(vec![pl.clone()])
+    );
+
+    reg.set_required_resources(all).unwrap();
+    // The browser resource hasn't been fetched from the toolkit source yet,
+    // so it's assumed to be provided.
+    assert_eq!(
+        reg.negotiate_locales(&requested, Some(&en_us), NegotiationStrategy::Filtering),
+        Ok(vec![pl.clone(), en_us.clone()])
+    );
+    assert!(reg
+        .lock()
+        .get_source("toolkit")
+        .unwrap()
+        .fetch_file_sync(&pl, FTL_RESOURCE_BROWSER, false)
+        .is_none());
+    assert_eq!(
+        reg.negotiate_locales(&requested, Some(&en_us), NegotiationStrategy::Filtering),
+        Ok(vec![en_us.clone()])
+    );
+    assert_eq!(
+        reg.negotiate_locales(&[de], Some(&en_us), NegotiationStrategy::Lookup),
+        Ok(vec![en_us.clone()])
+    );
+
+    reg.set_required_resources(with_optional).unwrap();
+    assert_eq!(
+        reg.negotiate_locales(&requested, None, NegotiationStrategy::Filtering),
+        Ok(vec![pl.clone(), en_us.clone()])
+    );
+}
+
+#[test]
+fn test_coverage_report() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let xx: LanguageIdentifier = "xx".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new(
+                "toolkit",
+                vec![en_us.clone(), xx.clone()],
+                "toolkit/{locale}/",
+            ),
+            FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (_, reg) = fetcher.get_registry_and_environment(setup);
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT.into(), FTL_RESOURCE_MISSING.into()];
+    let report = reg.coverage_report(&en_us, &paths).unwrap();
+    assert_eq!(report.locales.len(), 2);
+
+    let reference = report.get_locale(&en_us).unwrap();
+    assert_eq!(reference.resources.len(), 2);
+    let toolkit = &reference.resources[0];
+    assert_eq!(toolkit.sources, vec!["toolkit".to_string()]);
+    assert!(toolkit.message_count > 0);
+    assert!(toolkit.missing_messages.is_empty());
+    assert!(!reference.resources[1].is_available());
+    assert_eq!(reference.missing_message_count(), 0);
+    assert!(!reference.is_complete());
+
+    let other = report.get_locale(&xx).unwrap();
+    assert!(!other.resources[0].is_available());
+    assert_eq!(other.missing_message_count(), toolkit.message_count);
+
+    let tsv = report.to_tsv();
+    assert_eq!(tsv.lines().count(), 5);
+    assert!(tsv.contains(&format!("en-US\t{}\ttoolkit\t", FTL_RESOURCE_TOOLKIT)));
+
+    let mut report = report;
+    report.locales.truncate(1);
+    report.locales[0].resources.truncate(1);
+    report.locales[0].resources[0].res_id = "a\tb\nc\\d.ftl".to_string();
+    let tsv = report.to_tsv();
+    assert_eq!(tsv.lines().count(), 2);
+    assert!(tsv.contains("\ta\\tb\\nc\\\\d.ftl\t"));
+}
+
+#[test]
+fn test_missing_resource_diagnostics() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+            FileSource::new("langpack", vec!["pl".parse().unwrap()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (env, reg) = fetcher.get_registry_and_environment(setup);
+    reg.register_sources(vec![fetcher.get_test_file_source_with_index(
+        "browser",
+        vec![en_us.clone()],
+        "browser/{locale}/",
+        vec!["browser/en-US/branding/brand.ftl"],
+    )])
+    .unwrap();
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_MISSING];
+    let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths);
+    assert!(i.next().is_none());
+
+    let errors = env.errors();
+    assert_eq!(errors.len(), 1);
+    match &errors[0] {
+        L10nRegistryError::MissingResource { sources, .. } => {
+            let statuses: Vec<(&str, LookupStatus)> = sources
+                .iter()
+                .map(|lookup| (lookup.source.as_str(), lookup.status))
+                .collect();
+            assert_eq!(
+                statuses,
+                vec![
+                    ("browser", LookupStatus::NotInIndex),
+                    ("langpack", LookupStatus::LocaleNotDeclared),
+                    ("toolkit", LookupStatus::NotFound),
+                ]
+            );
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+    assert!(errors[0].to_string().contains(
+        "langpack (browser/{locale}/ as browser/en-US/missing.ftl): locale not declared"
+    ));
+}
+
+#[test]
+fn test_solver_options() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = || {
+        RegistrySetup::new(
+            "test",
+            vec![
+                FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+                FileSource::new("toolkit2", vec![en_us.clone()], "toolkit/{locale}/"),
+                FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+                FileSource::new("browser2", vec![en_us.clone()], "browser/{locale}/"),
+            ],
+            vec![en_us.clone()],
+        )
+    };
+    let fetcher = TestFileFetcher::new();
+    let paths = || vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+
+    let (_, mut reg) = fetcher.get_registry_and_environment(setup());
+    assert_eq!(
+        reg.generate_bundles_for_lang_sync(en_us.clone(), paths())
+            .count(),
+        4
+    );
+
+    reg.set_solver_options(SolverOptions {
+        max_solutions: None,
+        greedy: true,
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(
+        reg.generate_bundles_for_lang_sync(en_us.clone(), paths())
+            .count(),
+        3
+    );
+
+    let i = reg
+        .generate_bundles_for_lang_sync(en_us.clone(), paths())
+        .with_solver_options(SolverOptions {
+            max_solutions: Some(1),
+            greedy: false,
+            ..Default::default()
+        });
+    assert_eq!(i.count(), 1);
+}
+
+#[tokio::test]
+async fn test_shared_availability() {
+    use futures::stream::StreamExt;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![
+            FileSource::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/"),
+            FileSource::new("browser", vec![en_us.clone()], "browser/{locale}/"),
+        ],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (_, reg) = fetcher.get_registry_and_environment(setup);
+    let paths = || vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+
+    assert_eq!(
+        reg.known_availability(&en_us, FTL_RESOURCE_TOOLKIT, "toolkit"),
+        None
+    );
+
+    let i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths());
+    assert_eq!(i.count(), 1);
+    assert_eq!(
+        reg.known_availability(&en_us, FTL_RESOURCE_TOOLKIT, "toolkit"),
+        Some(true)
+    );
+    assert_eq!(
+        reg.known_availability(&en_us, FTL_RESOURCE_BROWSER, "toolkit"),
+        Some(false)
+    );
+
+    // Another generator starts from the results of the first one.
+    let mut i = reg.generate_bundles_for_lang(en_us.clone(), paths());
+    assert!(i.next().await.is_some());
+    assert!(i.next().await.is_none());
+
+    reg.clear_caches().unwrap();
+    assert_eq!(
+        reg.known_availability(&en_us, FTL_RESOURCE_TOOLKIT, "toolkit"),
+        None
+    );
+
+    let mut i = reg.generate_bundles_for_lang(en_us.clone(), paths());
+    assert!(i.next().await.is_some());
+    assert_eq!(
+        reg.known_availability(&en_us, FTL_RESOURCE_BROWSER, "browser"),
+        Some(true)
+    );
+
+    reg.remove_sources(vec!["browser"]).unwrap();
+    assert_eq!(
+        reg.known_availability(&en_us, FTL_RESOURCE_TOOLKIT, "toolkit"),
+        None
+    );
+}
+
+#[test]
+fn test_registry_from_config() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let config = RegistryConfig {
+        sources: vec![
+            FileSourceDescription {
+                name: "toolkit".to_string(),
+                locales: vec![en_us.clone()],
+                pre_path: "toolkit/{locale}/".parse().unwrap(),
+                index: None,
+                options: Default::default(),
+            },
+            FileSourceDescription {
+                name: "browser".to_string(),
+                locales: vec![en_us.clone()],
+                pre_path: "browser/{locale}/".parse().unwrap(),
+                index: None,
+                options: Default::default(),
+            },
+        ],
+        locale_fallback: Some(LocaleFallback {
+            default_locale: Some(en_us.clone()),
+            likely_subtags: false,
+        }),
+        solver_options: Default::default(),
+    };
+    let fetcher = TestFileFetcher::new();
+    let provider = TestEnvironment::new(vec![en_us.clone()]);
+
+    let mut pre_paths = vec![];
+    let reg: L10nRegistry<_, MockBundleAdapter> =
+        L10nRegistry::from_config(provider.clone(), config.clone(), |description| {
+            pre_paths.push(description.pre_path.to_string());
+            fetcher.clone()
+        })
+        .unwrap();
+    assert_eq!(pre_paths, vec!["toolkit/{locale}/", "browser/{locale}/"]);
+    assert_eq!(reg.get_config().unwrap(), config);
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+    let i = reg.generate_bundles_for_lang_sync("en-GB".parse().unwrap(), paths);
+    assert_eq!(i.count(), 1);
+
+    let mut duplicated = config;
+    duplicated.sources.push(duplicated.sources[0].clone());
+    assert_eq!(
+        L10nRegistry::<_, MockBundleAdapter>::from_config(provider, duplicated, |_| fetcher
+            .clone())
+        .err(),
+        Some(L10nRegistrySetupError::DuplicatedSource {
+            name: "toolkit".to_string()
+        })
+    );
+}
+
+#[test]
+fn test_change_listeners() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let setup = RegistrySetup::new(
+        "test",
+        vec![FileSource::new(
+            "toolkit",
+            vec![en_us.clone()],
+            "toolkit/{locale}/",
+        )],
+        vec![en_us.clone()],
+    );
+    let fetcher = TestFileFetcher::new();
+    let (_, reg) = fetcher.get_registry_and_environment(setup);
+
+    let received = Rc::new(RefCell::new(vec![]));
+    let inner_reg = reg.clone();
+    let inner_received = received.clone();
+    reg.add_change_listener(move |changes: &[ResourceChange]| {
+        inner_received.borrow_mut().push(changes.len());
+        // A listener may register another listener while being notified.
+        let nested_received = inner_received.clone();
+        inner_reg.add_change_listener(move |_: &[ResourceChange]| {
+            nested_received.borrow_mut().push(0);
+        });
+    });
+
+    let change = ResourceChange {
+        source: "toolkit".to_string(),
+        locale: en_us.clone(),
+        res_id: FTL_RESOURCE_TOOLKIT.to_string(),
+    };
+    reg.notify_changes(std::slice::from_ref(&change));
+    assert_eq!(*received.borrow(), vec![1]);
+
+    reg.notify_changes(&[change.clone(), change]);
+    assert_eq!(*received.borrow(), vec![1, 2, 0]);
+}
@@ -139,3 +139,47 @@ fn test_indexed() {
     assert!(fs1.fetch_file_sync(&en_us, path_missing, false).is_none());
     assert_eq!(fs1.has_file(&en_us, path_missing), Some(false));
 }
+
+#[test]
+fn test_invalidate() {
+    let fetcher = TestFileFetcher::new();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let path = FTL_RESOURCE_PRESENT;
+    let path_missing = FTL_RESOURCE_MISSING;
+
+    let fs1 = fetcher.get_test_file_source("toolkit", vec![en_us.clone()], "toolkit/{locale}/");
+
+    assert!(fs1.fetch_file_sync(&en_us, path, false).is_some());
+    assert!(fs1.fetch_file_sync(&en_us, path_missing, false).is_none());
+    assert_eq!(fs1.has_file(&en_us, path), Some(true));
+    assert_eq!(fs1.has_file(&en_us, path_missing), Some(false));
+
+    fs1.invalidate(&en_us, path);
+    assert_eq!(fs1.has_file(&en_us, path), None);
+    assert_eq!(fs1.has_file(&en_us, path_missing), Some(false));
+
+    assert!(fs1.fetch_file_sync(&en_us, path, false).is_some());
+    fs1.invalidate_locale(&en_us);
+    assert_eq!(fs1.has_file(&en_us, path), None);
+    assert_eq!(fs1.has_file(&en_us, path_missing), None);
+
+    assert!(fs1.fetch_file_sync(&en_us, path, false).is_some());
+    fs1.clear_cache();
+    assert_eq!(fs1.has_file(&en_us, path), None);
+}
+
+#[tokio::test]
+async fn test_invalidate_pending_async() {
+    let fetcher = TestFileFetcher::new();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+
+    let fs1 = fetcher.get_test_file_source("toolkit", vec![en_us.clone()], "toolkit/{locale}/");
+
+    let file = fs1.fetch_file(&en_us, FTL_RESOURCE_PRESENT);
+    fs1.invalidate(&en_us, FTL_RESOURCE_PRESENT);
+    assert!(file.await.is_some());
+    assert_eq!(fs1.has_file(&en_us, FTL_RESOURCE_PRESENT), None);
+
+    assert!(fs1.fetch_file(&en_us, FTL_RESOURCE_PRESENT).await.is_some());
+    assert_eq!(fs1.has_file(&en_us, FTL_RESOURCE_PRESENT), Some(true));
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use fluent_fallback::generator::{BundleGenerator, BundleIterator};
+use futures::stream::StreamExt;
+use l10nregistry::env::ErrorReporter;
+use l10nregistry::errors::L10nRegistryError;
+use l10nregistry::family::{Bundle, Family, IntoFetcher};
+use l10nregistry::registry::ResourceChange;
+use l10nregistry::source::{self, FileSourceOptions};
+use l10nregistry::sync::{
+    BundleAdapter, Concurrent, FileSource, FluentBundle, L10nRegistry, SendFileFetcher,
+};
+use unic_langid::LanguageIdentifier;
+
+const FTL_RESOURCE_TOOLKIT: &str = "toolkit/global/textActions.ftl";
+const FTL_RESOURCE_BROWSER: &str = "branding/brand.ftl";
+
+#[derive(Clone, Default)]
+struct MemoryFileFetcher {
+    files: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl MemoryFileFetcher {
+    fn new() -> Self {
+        let fetcher = Self::default();
+        fetcher.insert(
+            &format!("toolkit/en-US/{}", FTL_RESOURCE_TOOLKIT),
+            "copy = Copy\n",
+        );
+        fetcher.insert(
+            &format!("browser/en-US/{}", FTL_RESOURCE_BROWSER),
+            "brand-name = Firefox\n",
+        );
+        fetcher
+    }
+
+    fn insert(&self, path: &str, source: &str) {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), source.to_string());
+    }
+}
+
+#[async_trait]
+impl SendFileFetcher for MemoryFileFetcher {
+    fn fetch_sync(&self, path: &str) -> io::Result<String> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "File not found"))
+    }
+
+    async fn fetch(&self, path: &str) -> io::Result<String> {
+        self.fetch_sync(path)
+    }
+}
+
+#[derive(Clone, Default)]
+struct TestEnvironment {
+    errors: Arc<Mutex<Vec<L10nRegistryError>>>,
+}
+
+impl ErrorReporter for TestEnvironment {
+    fn report_errors(&self, errors: Vec<L10nRegistryError>) {
+        self.errors.lock().unwrap().extend(errors);
+    }
+}
+
+#[derive(Clone)]
+struct TestBundleAdapter;
+
+impl<M> BundleAdapter<Concurrent<M>> for TestBundleAdapter
+where
+    Concurrent<M>: Family,
+{
+    fn adapt_bundle(&self, bundle: &mut Bundle<Concurrent<M>>) {
+        bundle.set_use_isolating(false);
+    }
+}
+
+fn get_sources<F: Family>(fetcher: MemoryFileFetcher) -> Vec<source::FileSource<F>>
+where
+    MemoryFileFetcher: IntoFetcher<F>,
+{
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    vec![
+        source::FileSource::new(
+            "toolkit".to_string(),
+            vec![en_us.clone()],
+            "toolkit/{locale}/".parse().unwrap(),
+            Default::default(),
+            fetcher.clone(),
+        ),
+        source::FileSource::new(
+            "browser".to_string(),
+            vec![en_us],
+            "browser/{locale}/".parse().unwrap(),
+            Default::default(),
+            fetcher,
+        ),
+    ]
+}
+
+fn get_registry(fetcher: MemoryFileFetcher) -> L10nRegistry<TestEnvironment, TestBundleAdapter> {
+    let mut reg = L10nRegistry::with_provider(TestEnvironment::default());
+    reg.set_adapt_bundle(TestBundleAdapter)
+        .expect("Failed to set bundle adapter.");
+    reg.register_sources(get_sources(fetcher)).unwrap();
+    reg
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_send_sync() {
+    assert_send_sync::<FileSource>();
+    assert_send_sync::<L10nRegistry<TestEnvironment, TestBundleAdapter>>();
+    assert_send_sync::<FluentBundle>();
+}
+
+#[test]
+fn test_generate_bundles_sync() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let reg = get_registry(MemoryFileFetcher::new());
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let reg = reg.clone();
+            let en_us = en_us.clone();
+            std::thread::spawn(move || {
+                let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+                let mut i = reg.generate_bundles_for_lang_sync(en_us, paths);
+                let bundle = i
+                    .next()
+                    .unwrap()
+                    .unwrap_or_else(|_| panic!("Failed to build the bundle."));
+                assert!(bundle.has_message("copy"));
+                assert!(bundle.has_message("brand-name"));
+                assert!(i.next().is_none());
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_bundles() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let reg = get_registry(MemoryFileFetcher::new());
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let reg = reg.clone();
+            let en_us = en_us.clone();
+            tokio::spawn(async move {
+                let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+                let mut i = reg.generate_bundles_for_lang(en_us, paths);
+                let bundle = i
+                    .next()
+                    .await
+                    .unwrap()
+                    .unwrap_or_else(|_| panic!("Failed to build the bundle."));
+                assert!(bundle.has_message("copy"));
+                assert!(bundle.has_message("brand-name"));
+                assert!(i.next().await.is_none());
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.await.unwrap();
+    }
+}
+
+#[test]
+fn test_invalidate_resource() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    let reg = get_registry(fetcher.clone());
+
+    let changes = Arc::new(Mutex::new(vec![]));
+    let listener_changes = changes.clone();
+    reg.add_change_listener(move |c: &[ResourceChange]| {
+        listener_changes.lock().unwrap().extend_from_slice(c);
+    });
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT];
+    let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths.clone());
+    let bundle = i
+        .next()
+        .unwrap()
+        .unwrap_or_else(|_| panic!("Failed to build the bundle."));
+    assert!(!bundle.has_message("paste"));
+
+    fetcher.insert(
+        &format!("toolkit/en-US/{}", FTL_RESOURCE_TOOLKIT),
+        "copy = Copy\npaste = Paste\n",
+    );
+    let change = ResourceChange {
+        source: "toolkit".to_string(),
+        locale: en_us.clone(),
+        res_id: FTL_RESOURCE_TOOLKIT.to_string(),
+    };
+    let notifier = reg.clone();
+    std::thread::spawn(move || {
+        notifier
+            .invalidate_resource(&change.locale, &change.res_id)
+            .unwrap();
+        notifier.notify_changes(&[change]);
+    })
+    .join()
+    .unwrap();
+    assert_eq!(changes.lock().unwrap().len(), 1);
+
+    let mut i = reg.generate_bundles_for_lang_sync(en_us, paths);
+    let bundle = i
+        .next()
+        .unwrap()
+        .unwrap_or_else(|_| panic!("Failed to build the bundle."));
+    assert!(bundle.has_message("paste"));
+}
+
+#[test]
+fn test_bundle_generator() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let mut reg: l10nregistry::registry::L10nRegistry<
+        TestEnvironment,
+        TestBundleAdapter,
+        Concurrent<intl_memoizer::IntlLangMemoizer>,
+    > = l10nregistry::registry::L10nRegistry::with_provider(TestEnvironment::default());
+    reg.set_adapt_bundle(TestBundleAdapter)
+        .expect("Failed to set bundle adapter.");
+    reg.register_sources(get_sources(MemoryFileFetcher::new()))
+        .unwrap();
+
+    let paths = vec![
+        FTL_RESOURCE_TOOLKIT.to_string(),
+        FTL_RESOURCE_BROWSER.to_string(),
+    ];
+    let mut i = reg.bundles_iter(vec![en_us].into_iter(), paths);
+    i.prefetch_sync();
+    let bundle = i
+        .next()
+        .unwrap()
+        .unwrap_or_else(|_| panic!("Failed to build the bundle."));
+    assert!(bundle.has_message("copy"));
+    assert!(bundle.has_message("brand-name"));
+    assert!(i.next().is_none());
+}
+
+#[test]
+fn test_missing_ttl_availability() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    let mut options = FileSourceOptions::default();
+    options.cache.missing_ttl = Some(Duration::from_millis(50));
+    let reg: L10nRegistry<TestEnvironment, TestBundleAdapter> =
+        L10nRegistry::with_provider(TestEnvironment::default());
+    reg.register_sources(vec![FileSource::new(
+        "toolkit".to_string(),
+        vec![en_us.clone()],
+        "toolkit/{locale}/".parse().unwrap(),
+        options,
+        fetcher.clone(),
+    )])
+    .unwrap();
+
+    let paths = vec![FTL_RESOURCE_TOOLKIT, FTL_RESOURCE_BROWSER];
+    let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths.clone());
+    assert!(i.next().is_none());
+    assert_eq!(
+        reg.known_availability(&en_us, FTL_RESOURCE_BROWSER, "toolkit"),
+        Some(false)
+    );
+
+    // The resource is remembered as missing until the TTL expires.
+    fetcher.insert(
+        &format!("toolkit/en-US/{}", FTL_RESOURCE_BROWSER),
+        "brand-name = Firefox\n",
+    );
+    let mut i = reg.generate_bundles_for_lang_sync(en_us.clone(), paths.clone());
+    assert!(i.next().is_none());
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(
+        reg.known_availability(&en_us, FTL_RESOURCE_BROWSER, "toolkit"),
+        None
+    );
+    let mut i = reg.generate_bundles_for_lang_sync(en_us, paths);
+    let bundle = i
+        .next()
+        .unwrap()
+        .unwrap_or_else(|_| panic!("Failed to build the bundle."));
+    assert!(bundle.has_message("brand-name"));
+}